@@ -1,13 +1,27 @@
-use crate::protocol::{
-    event::{self, Event, MessageEvent},
-    get_bot,
+use crate::{
+    application::{
+        APPS, Priority, Propagation,
+        command::{self, Arg, Args, Command},
+        permission::{self, Permission},
+        switchboard::{self, Scope},
+    },
+    protocol::{
+        event::{EventKind, MessageEvent},
+        get_bot_by_id,
+    },
 };
 use anyhow::Result;
 use async_trait::async_trait;
-use std::{collections::HashMap, sync::Arc};
+use dashmap::{DashMap, mapref::entry::Entry};
+use std::sync::Arc;
+
+/// 帮助超过此行数时分页，以合并转发发送
+const HELP_PAGE_LINES: usize = 20;
 
 pub struct BuiltinApp {
-    group_map: HashMap<i64, String>,
+    /// 群名缓存，`None` 表示正在查询
+    group_map: Arc<DashMap<i64, Option<String>>>,
+    commands: Vec<Command>,
 }
 
 #[async_trait]
@@ -16,57 +30,373 @@ impl super::Application for BuiltinApp {
         "builtin"
     }
 
-    async fn on_event(&mut self, event: Arc<event::Event>) -> Result<()> {
-        match event.as_ref() {
-            Event::MessageEvent(event) => match event {
-                MessageEvent::Group(event) => {
-                    let group_name = self.get_group_name(event.group_id).await?;
-                    log::info!(
-                        "{}({}): {}({}) -> {}",
-                        group_name,
-                        event.group_id,
-                        event.sender.nickname,
-                        event.sender.user_id,
-                        event.message
-                    )
-                }
+    fn subscriptions(&self) -> &[EventKind] {
+        &[EventKind::Message]
+    }
 
-                MessageEvent::Private(event) => {
-                    log::info!(
-                        "{}({}) -> {}",
-                        event.sender.nickname,
-                        event.sender.user_id,
-                        event.message
-                    );
-                }
-            },
-            _ => {}
-        };
+    /// 最先处理，以便记录每条消息
+    fn priority(&self) -> Priority {
+        Priority::Blocking(0)
+    }
+
+    fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// `permission_file`、`switch_file` 可能换了路径，重新读取角色与开关
+    async fn on_config_reload(&self) -> Result<()> {
+        permission::reload();
+        switchboard::reload();
         Ok(())
     }
+
+    async fn on_message(&self, event: &MessageEvent) -> Result<Propagation> {
+        let mut propagation = Propagation::Continue;
+        if let Some((command, args)) = command::route(&self.commands, event).await? {
+            propagation = Propagation::Stop;
+            // 命令出错时仍然停止传播，以免后面的应用再处理同一条命令
+            if let Err(e) = run_command(&command.name, &args, event).await {
+                log::error!("builtin command <{}> failed: {}", command.name, e);
+            }
+        }
+
+        match event {
+            MessageEvent::Group(event) => {
+                let group_name = self.group_name(event.base.self_id, event.group_id);
+                log::info!(
+                    "{}({}): {}({}) -> {}",
+                    group_name,
+                    event.group_id,
+                    event.sender.nickname,
+                    event.sender.user_id,
+                    event.message
+                )
+            }
+
+            MessageEvent::Private(event) => {
+                log::info!(
+                    "{}({}) -> {}",
+                    event.sender.nickname,
+                    event.sender.user_id,
+                    event.message
+                );
+            }
+        }
+        Ok(propagation)
+    }
 }
 
 impl BuiltinApp {
     pub fn new() -> Self {
         Self {
-            group_map: HashMap::new(),
+            group_map: Arc::new(DashMap::new()),
+            commands: vec![
+                Command::new("help", "列出可用的命令").alias("帮助"),
+                Command::new(
+                    "app",
+                    "查看应用开关，action 为 enable 或 disable 时在本群（私聊）启用、停用应用",
+                )
+                .arg(Arg::word("action").optional())
+                .arg(Arg::word("app").optional())
+                .permission(Permission::GroupAdmin),
+                Command::new("grant", "授予角色，admin 或 superuser")
+                    .arg(Arg::user("user"))
+                    .arg(Arg::word("role"))
+                    .permission(Permission::Owner),
+                Command::new("revoke", "撤销授予的角色")
+                    .arg(Arg::user("user"))
+                    .permission(Permission::Owner),
+            ],
         }
     }
 
-    async fn get_group_name(&mut self, group_id: i64) -> Result<&String> {
-        if !self.group_map.contains_key(&group_id) {
-            let bot = get_bot().await;
-            let res = bot.get_group_info(group_id, false).await?;
-            let group_name = res
-                .data
-                .as_ref()
-                .and_then(|d| d.get("group_name"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("<unknown>")
-                .to_string();
-            self.group_map.insert(group_id, group_name);
+    /// 返回缓存的群名，未缓存时先以群号代替，并在后台查询，不阻塞后面的应用
+    fn group_name(&self, self_id: i64, group_id: i64) -> String {
+        match self.group_map.entry(group_id) {
+            Entry::Occupied(entry) => match entry.get() {
+                Some(name) => return name.clone(),
+                None => return group_id.to_string(),
+            },
+            Entry::Vacant(entry) => _ = entry.insert(None),
+        }
+        let group_map = self.group_map.clone();
+        tokio::spawn(async move {
+            match get_bot_by_id(self_id).get_group_info(group_id, false).await {
+                Ok(info) => _ = group_map.insert(group_id, Some(info.group_name)),
+                Err(e) => {
+                    log::warn!("failed to get name of group {}: {}", group_id, e);
+                    group_map.remove(&group_id);
+                }
+            }
+        });
+        group_id.to_string()
+    }
+}
+
+async fn run_command(name: &str, args: &Args, event: &MessageEvent) -> Result<()> {
+    match name {
+        "help" => help(event).await?,
+        "app" => manage_apps(event, args.text("action"), args.text("app")).await?,
+        "grant" => {
+            let user = args.user("user").unwrap_or_default();
+            let reply = match args.text("role").and_then(Permission::parse_role) {
+                Some(role) => {
+                    permission::grant(user, role)?;
+                    format!("已授予 {} {}", user, role)
+                }
+                None => "角色只能是 admin 或 superuser".to_string(),
+            };
+            event.reply(reply, true).await?;
+        }
+        "revoke" => {
+            let user = args.user("user").unwrap_or_default();
+            let reply = if permission::revoke(user)? {
+                format!("已撤销 {} 的角色", user)
+            } else {
+                format!("{} 没有被授予角色", user)
+            };
+            event.reply(reply, true).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 列出提问者有权限执行、且应用在当前群或私聊中启用的所有命令，过长时分页
+async fn help(event: &MessageEvent) -> Result<()> {
+    let permission = Permission::of(event);
+    let scope = Scope::of(event);
+    let mut lines = Vec::new();
+    for app in APPS.iter() {
+        if !switchboard::enabled(app.name(), scope, app.default_enabled()) {
+            continue;
+        }
+        let commands: Vec<&Command> = app
+            .commands()
+            .iter()
+            .filter(|c| permission >= c.permission)
+            .collect();
+        if commands.is_empty() {
+            continue;
+        }
+        lines.push(format!("[{}]", app.name()));
+        for command in commands {
+            let mut line = format!("{}  {}", command.usage(), command.description);
+            if !command.aliases.is_empty() {
+                line.push_str(&format!("（别名: {}）", command.aliases.join(", ")));
+            }
+            if command.permission > Permission::Everyone {
+                line.push_str(&format!("（{}）", command.permission));
+            }
+            lines.push(line);
+        }
+    }
+
+    let pages: Vec<String> = lines
+        .chunks(HELP_PAGE_LINES)
+        .map(|page| page.join("\n"))
+        .collect();
+    if pages.len() <= 1 {
+        event.reply(pages.concat(), true).await?;
+        return Ok(());
+    }
+    send_pages(event, pages).await
+}
+
+/// 列出应用在当前群或私聊的开关，或启用、停用指定应用
+async fn manage_apps(event: &MessageEvent, action: Option<&str>, name: Option<&str>) -> Result<()> {
+    let scope = Scope::of(event);
+    let mut states = Vec::new();
+    for app in APPS.iter() {
+        let enabled = switchboard::enabled(app.name(), scope, app.default_enabled());
+        states.push((app.name().to_string(), enabled));
+    }
+
+    let reply = match (action, name) {
+        (None, None) => {
+            let mut reply = format!("{}的应用:", scope);
+            for (name, enabled) in states {
+                let state = if enabled { "开启" } else { "关闭" };
+                reply.push_str(&format!("\n{}: {}", name, state));
+            }
+            reply
+        }
+        (Some(action @ ("enable" | "disable")), Some(name)) => {
+            let enable = action == "enable";
+            if !states.iter().any(|(n, _)| n == name) {
+                format!("没有应用 {}", name)
+            } else if name == "builtin" && !enable {
+                "builtin 不能停用".to_string()
+            } else {
+                switchboard::set(name, scope, enable)?;
+                let state = if enable { "开启" } else { "关闭" };
+                format!("已在{}{} {}", scope, state, name)
+            }
         }
+        _ => "用法: !app [enable|disable] [app]".to_string(),
+    };
+    event.reply(reply, true).await?;
+    Ok(())
+}
+
+#[cfg(feature = "napcat")]
+async fn send_pages(event: &MessageEvent, pages: Vec<String>) -> Result<()> {
+    use crate::protocol::message::Segment;
 
-        Ok(unsafe { self.group_map.get(&group_id).unwrap_unchecked() })
+    let nodes: Vec<Segment> = pages
+        .into_iter()
+        .map(|page| Segment::custom_node(event.self_id(), "帮助", page))
+        .collect();
+    let bot = get_bot_by_id(event.self_id());
+    match event {
+        MessageEvent::Group(e) => {
+            bot.send_forward_msg(Some("group"), None, Some(e.group_id), nodes)
+                .await?
+        }
+        MessageEvent::Private(e) => {
+            bot.send_forward_msg(Some("private"), Some(e.user_id), None, nodes)
+                .await?
+        }
+    };
+    Ok(())
+}
+
+/// 不支持合并转发时逐页发送
+#[cfg(not(feature = "napcat"))]
+async fn send_pages(event: &MessageEvent, pages: Vec<String>) -> Result<()> {
+    for page in pages {
+        event.reply(page, false).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        application::{
+            permission::{self, Permission},
+            switchboard::{self, Scope},
+        },
+        protocol::mock::{MockServer, group_message, ok, private_message},
+    };
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn group_name_is_cached() {
+        let server = MockServer::start(10003, "token = \"\"\nowner = 1\n").await;
+        server.respond(
+            "get_group_info",
+            ok(json!({
+                "group_id": 200,
+                "group_name": "test",
+                "member_count": 1,
+                "max_member_count": 200,
+            })),
+        );
+        server.push(group_message(200, 2, "hello"));
+        server.wait_action("get_group_info").await;
+        server.push(group_message(200, 2, "world"));
+        server.push(group_message(201, 2, "other group"));
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let lookups: Vec<_> = server
+            .actions()
+            .into_iter()
+            .filter(|a| a.action == "get_group_info")
+            .map(|a| a.params["group_id"].clone())
+            .collect();
+        assert_eq!(lookups, [json!(200), json!(201)]);
+    }
+
+    #[tokio::test]
+    async fn failed_group_lookup_does_not_block_commands() {
+        let server = MockServer::start(10009, "token = \"\"\nowner = 1\n").await;
+        server.push(group_message(400, 2, "!help"));
+        server.wait_action("send_group_msg").await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        server.push(group_message(400, 2, "hello"));
+
+        let lookups = server.wait_actions("get_group_info", 2).await;
+        assert!(lookups.iter().all(|a| a.params["group_id"] == 400));
+    }
+
+    #[tokio::test]
+    async fn help_hides_owner_commands() {
+        let server = MockServer::start(10004, "token = \"\"\nowner = 1\n").await;
+        server.push(group_message(300, 2, "!help"));
+
+        let sent = server.wait_action("send_group_msg").await;
+        let text = sent.params["message"][1]["data"]["text"].as_str().unwrap();
+        assert!(text.contains("!help  列出可用的命令"));
+        assert!(text.contains("!perf"));
+        assert!(!text.contains("!switch"));
+    }
+
+    #[tokio::test]
+    async fn help_hides_disabled_apps() {
+        let file = std::env::temp_dir().join("kanami-test-help-switches.json");
+        _ = std::fs::remove_file(&file);
+        let config = format!(
+            "token = \"\"\nowner = 1\nswitch_file = {:?}\n",
+            file.display().to_string()
+        );
+        let server = MockServer::start(10018, &config).await;
+        server.push(group_message(301, 2, "!help"));
+        let sent = server.wait_action("send_group_msg").await;
+        let text = sent.params["message"][1]["data"]["text"].as_str().unwrap();
+        assert!(!text.contains("!ai"));
+        assert!(text.contains("!perf"));
+
+        switchboard::set("chat", Scope::Group(301), true).unwrap();
+        server.push(group_message(301, 2, "!help"));
+        let sent = server.wait_actions("send_group_msg", 2).await.remove(1);
+        let text = sent.params["message"][1]["data"]["text"].as_str().unwrap();
+        assert!(text.contains("!ai"));
+        _ = std::fs::remove_file(&file);
+    }
+
+    #[tokio::test]
+    async fn owner_grants_superuser() {
+        let file = std::env::temp_dir().join("kanami-test-permissions.json");
+        let config = format!(
+            "token = \"\"\nowner = [1, 3]\npermission_file = {:?}\n",
+            file.display().to_string()
+        );
+        let server = MockServer::start(10005, &config).await;
+        server.push(private_message(2, "!grant 42 superuser"));
+        server.push(private_message(3, "!grant 42 superuser"));
+
+        let sent = server.wait_action("send_private_msg").await;
+        assert_eq!(sent.params["user_id"], 3);
+        assert_eq!(permission::level(42, None), Permission::Superuser);
+        let saved = std::fs::read_to_string(&file).unwrap();
+        assert!(saved.contains("\"42\": \"superuser\""));
+        assert!(permission::revoke(42).unwrap());
+    }
+
+    #[tokio::test]
+    async fn long_help_is_paged() {
+        let server = MockServer::start(10020, "token = \"\"\nowner = 1\n").await;
+        let mut raw = group_message(302, 2, "!help");
+        raw["time"] = 0.into();
+        raw["self_id"] = 10020.into();
+        raw["message_id"] = 0.into();
+        let event = serde_json::from_value(raw).unwrap();
+        super::send_pages(&event, vec!["第一页".to_string(), "第二页".to_string()])
+            .await
+            .unwrap();
+
+        #[cfg(feature = "napcat")]
+        {
+            let sent = server.wait_action("send_msg").await;
+            let nodes = sent.params["message"].as_array().unwrap();
+            assert_eq!(nodes.len(), 2);
+            assert_eq!(nodes[1]["data"]["content"][0]["data"]["text"], "第二页");
+        }
+        #[cfg(not(feature = "napcat"))]
+        {
+            let sent = server.wait_actions("send_group_msg", 2).await;
+            assert_eq!(sent[1].params["message"][0]["data"]["text"], "第二页");
+        }
     }
 }
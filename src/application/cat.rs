@@ -1,11 +1,12 @@
-use std::sync::Arc;
+use std::sync::RwLock;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::Deserialize;
 
+use crate::application::Propagation;
 use crate::config;
-use crate::protocol::event::{Event, MessageEvent};
+use crate::protocol::event::{EventKind, MessageEvent};
 use crate::protocol::message::Segment;
 
 #[derive(Deserialize)]
@@ -13,7 +14,23 @@ struct CatApiResponse {
     url: String,
 }
 
-pub struct CatApp;
+#[derive(Deserialize)]
+#[serde(default)]
+struct CatConfig {
+    api: String,
+}
+
+impl Default for CatConfig {
+    fn default() -> Self {
+        Self {
+            api: "https://api.thecatapi.com/v1/images/search".to_string(),
+        }
+    }
+}
+
+pub struct CatApp {
+    settings: RwLock<CatConfig>,
+}
 
 #[async_trait]
 impl super::Application for CatApp {
@@ -21,27 +38,39 @@ impl super::Application for CatApp {
         "cat"
     }
 
-    async fn on_event(&mut self, event: Arc<Event>) -> Result<()> {
-        if let Event::MessageEvent(msg_event) = event.as_ref() {
-            self.handle_message_event(msg_event).await?;
-        }
+    fn subscriptions(&self) -> &[EventKind] {
+        &[EventKind::Message]
+    }
+
+    async fn on_startup(&self) -> Result<()> {
+        self.on_config_reload().await?;
+        log::info!("app <{}> loaded", self.name());
         Ok(())
     }
+
+    fn default_enabled(&self) -> bool {
+        false
+    }
+
+    async fn on_config_reload(&self) -> Result<()> {
+        *self.settings.write().unwrap() = config::get().app("cat")?;
+        Ok(())
+    }
+
+    async fn on_message(&self, event: &MessageEvent) -> Result<Propagation> {
+        self.handle_message_event(event).await?;
+        Ok(Propagation::Continue)
+    }
 }
 
 impl CatApp {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            settings: RwLock::default(),
+        }
     }
 
     async fn handle_message_event(&self, event: &MessageEvent) -> Result<()> {
-        if let MessageEvent::Group(event) = event {
-            if !config::WHITE_GROUPS.contains(&event.group_id) {
-                // white list mode
-                return Ok(());
-            }
-        }
-
         let raw_message = event.raw_message();
         let count = raw_message.matches('喵').count();
 
@@ -53,7 +82,7 @@ impl CatApp {
             let images_to_send: Vec<Segment> = img_urls
                 .into_iter()
                 .take(count)
-                .map(|url| Segment::image(url))
+                .map(Segment::image)
                 .collect();
 
             if !images_to_send.is_empty() {
@@ -67,12 +96,9 @@ impl CatApp {
     }
 
     async fn fetch_cat_images(&self, limit: usize) -> Result<Vec<String>> {
+        let api = self.settings.read().unwrap().api.clone();
         let client = reqwest::Client::new();
-        let response = client
-            .get("https://api.thecatapi.com/v1/images/search")
-            .query(&[("limit", limit)])
-            .send()
-            .await?;
+        let response = client.get(&api).query(&[("limit", limit)]).send().await?;
 
         log::debug!("Cat API request with limit: {}", limit);
 
@@ -84,3 +110,38 @@ impl CatApp {
         Ok(img_urls)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::mock::{MockServer, group_message, http_fixture};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn meow_sends_images() {
+        let api = http_fixture(json!([
+            { "url": "https://cdn2.thecatapi.com/images/a.jpg" },
+            { "url": "https://cdn2.thecatapi.com/images/b.jpg" },
+        ]))
+        .await;
+        let switches = std::env::temp_dir().join("kanami-test-switches.json");
+        _ = std::fs::remove_file(&switches);
+        let config = format!(
+            "token = \"\"\nowner = 1\nswitch_file = {:?}\n[apps.cat]\napi = \"{}\"\n",
+            switches.display().to_string(),
+            api
+        );
+        let server = MockServer::start(10001, &config).await;
+        server.push(group_message(101, 2, "喵"));
+        server.push(group_message(100, 1, "!app enable cat"));
+        server.wait_action("send_group_msg").await;
+        server.push(group_message(100, 2, "喵喵"));
+
+        let sent = server.wait_actions("send_group_msg", 2).await.remove(1);
+        assert_eq!(sent.params["group_id"], 100);
+        let segments = sent.params["message"].as_array().unwrap();
+        assert_eq!(segments.len(), 2);
+        assert!(segments.iter().all(|s| s["type"] == "image"));
+        let sent = server.wait_actions("send_group_msg", 2).await;
+        assert!(sent.iter().all(|a| a.params["group_id"] == 100));
+    }
+}
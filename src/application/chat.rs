@@ -10,22 +10,28 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tokio::sync::RwLock;
-use tokio::time::Instant;
+use tokio::time::{Duration, Instant};
 
 use crate::{
+    application::{
+        Priority, Propagation,
+        command::{self, Arg, Command},
+        permission::Permission,
+        session,
+    },
     config,
     protocol::{
-        event::{Event, MessageEvent},
-        get_bot,
+        event::{EventKind, MessageEvent},
+        get_bot_by_id,
         message::{Message, Segment},
     },
 };
 
 const SYSTEM_PROMPT: &str = "你是一个AI助手，名字叫 Chihaya Anon。你的回答需要遵守中国法律，拒绝回答任何跟政治有关的问题以及涉嫌人身霸凌的问题。若无指定，使用中文进行回答。你的回答为无代码块包裹的rst格式。不要使用粗体和斜体，除非你有充分的理由那么做。";
-const RATE_LIMIT_WINDOW: u64 = 60; // seconds
-const RATE_LIMIT_MAX: usize = 3;
 const HISTORY_MAX_LENGTH: usize = 6;
 const MAX_MESSAGE_LENGTH: usize = 2800;
+/// `!switch` 等待回复模型名称的时间
+const SWITCH_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
@@ -56,25 +62,42 @@ struct Choice {
     message: ChatMessage,
 }
 
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+struct ChatConfig {
+    openai_token: String,
+    openai_base: String,
+    /// 限流窗口（秒）
+    #[serde(deserialize_with = "config::lenient")]
+    rate_limit_window: u64,
+    /// 窗口内每人最多请求次数
+    #[serde(deserialize_with = "config::lenient")]
+    rate_limit_max: usize,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            openai_token: String::new(),
+            openai_base: "https://api.openai.com".to_string(),
+            rate_limit_window: 60,
+            rate_limit_max: 3,
+        }
+    }
+}
+
 // Helper struct to reduce parameter passing
 struct ChatContext {
     client: Client,
-    token: String,
-    base_url: String,
+    settings: ChatConfig,
     current_model: Arc<RwLock<String>>,
 }
 
 impl ChatContext {
-    fn new(
-        client: Client,
-        token: String,
-        base_url: String,
-        current_model: Arc<RwLock<String>>,
-    ) -> Self {
+    fn new(client: Client, settings: ChatConfig, current_model: Arc<RwLock<String>>) -> Self {
         Self {
             client,
-            token,
-            base_url,
+            settings,
             current_model,
         }
     }
@@ -82,11 +105,11 @@ impl ChatContext {
 
 pub struct ChatApp {
     client: Client,
-    token: String,
-    base_url: String,
+    settings: RwLock<ChatConfig>,
     current_model: Arc<RwLock<String>>,
     history: Arc<DashMap<i64, Vec<ChatMessage>>>,
     rate_limiter: Arc<DashMap<i64, Vec<Instant>>>,
+    commands: Vec<Command>,
 }
 
 #[async_trait]
@@ -95,25 +118,62 @@ impl super::Application for ChatApp {
         "chat"
     }
 
-    async fn on_event(&mut self, event: Arc<Event>) -> Result<()> {
-        // Create context for concurrent processing
+    fn subscriptions(&self) -> &[EventKind] {
+        &[EventKind::Message]
+    }
+
+    async fn on_startup(&self) -> Result<()> {
+        self.on_config_reload().await?;
+        log::info!("app <{}> loaded", self.name());
+        Ok(())
+    }
+
+    async fn on_config_reload(&self) -> Result<()> {
+        *self.settings.write().await = config::get().app("chat")?;
+        Ok(())
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Blocking(20)
+    }
+
+    fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    fn default_enabled(&self) -> bool {
+        false
+    }
+
+    async fn on_message(&self, event: &MessageEvent) -> Result<Propagation> {
+        let Some((command, args)) = command::route(&self.commands, event).await? else {
+            return Ok(Propagation::Continue);
+        };
+        let cmd = command.name.clone();
+        let prompt = args
+            .text("prompt")
+            .or(args.text("model"))
+            .unwrap_or_default()
+            .to_string();
+
         let context = ChatContext::new(
             self.client.clone(),
-            self.token.clone(),
-            self.base_url.clone(),
+            self.settings.read().await.clone(),
             Arc::clone(&self.current_model),
         );
         let history = Arc::clone(&self.history);
         let rate_limiter = Arc::clone(&self.rate_limiter);
 
-        // Spawn a task for concurrent processing
+        // 请求模型与等待切换都可能很久，在后台处理，命中命令即停止传播；出错时仍视为已处理
+        let event = event.clone();
         tokio::spawn(async move {
-            if let Err(e) = Self::handle_event_impl(context, history, rate_limiter, event).await {
+            if let Err(e) =
+                Self::handle_event_impl(context, history, rate_limiter, &event, cmd, prompt).await
+            {
                 log::error!("Error handling chat event: {}", e);
             }
         });
-
-        Ok(())
+        Ok(Propagation::Stop)
     }
 }
 
@@ -122,170 +182,163 @@ impl ChatApp {
         context: ChatContext,
         history: Arc<DashMap<i64, Vec<ChatMessage>>>,
         rate_limiter: Arc<DashMap<i64, Vec<Instant>>>,
-        event: Arc<Event>,
+        event: &MessageEvent,
+        cmd: String,
+        mut prompt: String,
     ) -> Result<()> {
-        if let Event::MessageEvent(event) = event.as_ref() {
-            if let MessageEvent::Group(g) = event {
-                if !config::WHITE_GROUPS.contains(&g.group_id) {
-                    return Ok(());
-                }
-            }
+        let user_id = event.user_id();
 
-            let segments = event.message().segments();
-            let (mut text_prompt, mut images_to_process) = Self::extract_text_and_images(segments);
-
-            // rework if msg contains reply
-            if !segments.is_empty() {
-                if let Segment::Reply { id } = &segments[0] {
-                    let bot = get_bot().await;
-                    if let Some(data) = bot.get_message(id.parse()?).await?.data {
-                        let msg: Vec<Segment> =
-                            serde_json::from_value(data.get("message").unwrap().clone())?;
-                        let (a, mut b) = Self::extract_text_and_images(&msg);
-                        text_prompt.push_str(&a);
-                        images_to_process.append(&mut b);
+        if cmd == "switch" {
+            if prompt.is_empty() {
+                // Show current model and wait for the name of the next one
+                let next = session::wait_for_next_message(event, SWITCH_TIMEOUT);
+                let current_model = context.current_model.read().await.clone();
+                event
+                    .reply(
+                        format!(
+                            "当前模型: {}，{} 秒内回复模型名称以切换",
+                            current_model,
+                            SWITCH_TIMEOUT.as_secs()
+                        ),
+                        true,
+                    )
+                    .await?;
+                match next.await {
+                    Some(reply) if !reply.raw_message().trim().is_empty() => {
+                        prompt = reply.raw_message().trim().to_string()
                     }
+                    _ => return Ok(()),
                 }
             }
 
-            let (cmd, prompt) = Self::parse_command(&text_prompt);
-            if !matches!(cmd, "!ai" | "!aip" | "!switch") {
-                return Ok(());
+            {
+                let mut model = context.current_model.write().await;
+                *model = prompt.clone();
             }
 
-            debug!(
-                "Message received - user_id: {}, command: '{}', prompt: '{}', images: {}",
-                event.user_id(),
-                cmd,
-                prompt,
-                images_to_process.len()
-            );
+            event
+                .reply(format!("已切换模型为: {}", prompt), true)
+                .await?;
+            return Ok(());
+        }
 
-            let user_id = event.user_id();
+        let segments = event.message().segments();
+        let (_, mut images_to_process) = Self::extract_text_and_images(segments);
 
-            // Handle !switch command separately for OWNER only
-            if cmd == "!switch" {
-                if user_id != config::OWNER {
-                    return Ok(());
-                }
+        // rework if msg contains reply
+        if let Some(Segment::Reply { id }) = segments.first() {
+            let bot = get_bot_by_id(event.self_id());
+            let detail = bot.get_message(id.parse()?).await?;
+            let (a, mut b) = Self::extract_text_and_images(detail.message.segments());
+            prompt.push_str(&a);
+            images_to_process.append(&mut b);
+        }
 
-                if prompt.is_empty() {
-                    // Show current model when no parameter is provided
-                    let current_model = context.current_model.read().await.clone();
-                    event
-                        .reply(
-                            format!("当前模型: {}，输入模型名称以切换", current_model),
-                            true,
-                        )
-                        .await?;
-                    return Ok(());
-                }
+        debug!(
+            "Message received - user_id: {}, command: '{}', prompt: '{}', images: {}",
+            user_id,
+            cmd,
+            prompt,
+            images_to_process.len()
+        );
 
-                {
-                    let mut model = context.current_model.write().await;
-                    *model = prompt.to_string();
-                }
+        // Check rate limit
+        if let Err(msg) = Self::check_rate_limit(&context.settings, &rate_limiter, user_id).await {
+            event.reply(msg, true).await?;
+            return Ok(());
+        }
 
-                event
-                    .reply(format!("已切换模型为: {}", prompt), true)
-                    .await?;
-                return Ok(());
-            }
+        if prompt.is_empty() && images_to_process.is_empty() {
+            return Ok(());
+        }
 
-            // Check rate limit
-            if let Err(msg) = Self::check_rate_limit(&rate_limiter, user_id).await {
-                event.reply(msg, true).await?;
-                return Ok(());
-            }
+        let mut data_urls = Vec::new();
+        for (file_name, url) in images_to_process {
+            let response = context.client.get(&url).send().await?;
+            let image_bytes = response.bytes().await?;
+            let mime_type = Self::get_mime_type(&file_name);
+            let encoded_image = general_purpose::STANDARD.encode(&image_bytes);
+            let data_url = format!("data:{};base64,{}", mime_type, encoded_image);
+            data_urls.push(data_url);
+        }
 
-            if prompt.is_empty() && images_to_process.is_empty() {
-                return Ok(());
-            }
+        let user_content = Self::create_user_content(&prompt, data_urls);
+        let user_message = ChatMessage {
+            role: "user".to_string(),
+            content: user_content,
+        };
 
-            let mut data_urls = Vec::new();
-            for (file_name, url) in images_to_process {
-                let response = context.client.get(&url).send().await?;
-                let image_bytes = response.bytes().await?;
-                let mime_type = Self::get_mime_type(&file_name);
-                let encoded_image = general_purpose::STANDARD.encode(&image_bytes);
-                let data_url = format!("data:{};base64,{}", mime_type, encoded_image);
-                data_urls.push(data_url);
+        match cmd.as_str() {
+            "ai" => {
+                let mut messages = vec![Self::create_system_message()];
+                messages.push(user_message);
+                Self::execute_chat_and_reply(&context, event, &mut messages).await?;
+                history.insert(user_id, messages);
             }
-
-            let user_content = Self::create_user_content(prompt, data_urls);
-            let user_message = ChatMessage {
-                role: "user".to_string(),
-                content: user_content,
-            };
-
-            match cmd {
-                "!ai" => {
-                    let mut messages = vec![Self::create_system_message()];
-                    messages.push(user_message);
-                    Self::execute_chat_and_reply(&context, event, &mut messages).await?;
-                    history.insert(user_id, messages);
+            "aip" => {
+                let mut hist = history.entry(user_id).or_default();
+                debug!(
+                    "!aip command - user_id: {}, current history length: {}",
+                    user_id,
+                    hist.len()
+                );
+                if hist.is_empty() {
+                    hist.push(Self::create_system_message());
+                    debug!("!aip - initialized empty history with system message");
                 }
-                "!aip" => {
-                    let mut hist = history.entry(user_id).or_default();
+                if hist.len() >= HISTORY_MAX_LENGTH {
                     debug!(
-                        "!aip command - user_id: {}, current history length: {}",
-                        user_id,
-                        hist.len()
+                        "!aip - history length ({}) >= max length ({}), summarizing history",
+                        hist.len(),
+                        HISTORY_MAX_LENGTH
                     );
-                    if hist.is_empty() {
-                        hist.push(Self::create_system_message());
-                        debug!("!aip - initialized empty history with system message");
-                    }
-                    if hist.len() >= HISTORY_MAX_LENGTH {
-                        debug!(
-                            "!aip - history length ({}) >= max length ({}), summarizing history",
-                            hist.len(),
-                            HISTORY_MAX_LENGTH
-                        );
-                        let summary = Self::summarize_history(&context, &hist).await?;
-                        debug!("!aip - generated summary: {}", summary);
-                        *hist = vec![
-                            Self::create_system_message(),
-                            ChatMessage {
-                                role: "system".to_string(),
-                                content: ChatContent::Text(format!(
-                                    "这是之前对话的摘要: {}",
-                                    summary
-                                )),
-                            },
-                        ];
-                        debug!(
-                            "!aip - reset history with summary, new length: {}",
-                            hist.len()
-                        );
-                    }
-                    hist.push(user_message);
+                    let summary = Self::summarize_history(&context, &hist).await?;
+                    debug!("!aip - generated summary: {}", summary);
+                    *hist = vec![
+                        Self::create_system_message(),
+                        ChatMessage {
+                            role: "system".to_string(),
+                            content: ChatContent::Text(format!("这是之前对话的摘要: {}", summary)),
+                        },
+                    ];
                     debug!(
-                        "!aip - added user message, history length now: {}",
-                        hist.len()
-                    );
-                    Self::execute_chat_and_reply(&context, event, &mut hist).await?;
-                    debug!(
-                        "!aip - after AI response, final history length: {}",
+                        "!aip - reset history with summary, new length: {}",
                         hist.len()
                     );
                 }
-                _ => {}
+                hist.push(user_message);
+                debug!(
+                    "!aip - added user message, history length now: {}",
+                    hist.len()
+                );
+                Self::execute_chat_and_reply(&context, event, &mut hist).await?;
+                debug!(
+                    "!aip - after AI response, final history length: {}",
+                    hist.len()
+                );
             }
+            _ => {}
         }
         Ok(())
     }
 }
 
 impl ChatApp {
-    pub fn new(token: &str, base_url: &str) -> Self {
+    pub fn new() -> Self {
         Self {
             client: Client::new(),
-            token: token.to_string(),
-            base_url: base_url.to_string(),
+            settings: RwLock::default(),
             current_model: Arc::new(RwLock::new("claude-sonnet-4-20250514".to_string())),
             history: Arc::new(DashMap::new()),
             rate_limiter: Arc::new(DashMap::new()),
+            commands: vec![
+                Command::new("ai", "和 AI 对话，可附带或引用图片")
+                    .arg(Arg::rest("prompt").optional()),
+                Command::new("aip", "带上下文和 AI 对话").arg(Arg::rest("prompt").optional()),
+                Command::new("switch", "查看或切换模型")
+                    .arg(Arg::word("model").optional())
+                    .permission(Permission::Owner),
+            ],
         }
     }
 
@@ -305,10 +358,20 @@ impl ChatApp {
                 Segment::Text { text } => {
                     text_prompt.push_str(text);
                 }
-                Segment::Image { file, url, .. } => {
-                    if let Some(url) = url {
-                        images_to_process.push((file.clone(), url.clone()));
-                    }
+                Segment::Image {
+                    file,
+                    url: Some(url),
+                    ..
+                } => {
+                    images_to_process.push((file.to_string(), url.clone()));
+                }
+                #[cfg(feature = "napcat")]
+                Segment::Markdown { content } => {
+                    text_prompt.push_str(content);
+                }
+                #[cfg(feature = "napcat")]
+                Segment::Mface { url: Some(url), .. } => {
+                    images_to_process.push((url.clone(), url.clone()));
                 }
                 _ => {}
             }
@@ -317,15 +380,8 @@ impl ChatApp {
         (text_prompt, images_to_process)
     }
 
-    fn parse_command(text: &str) -> (&str, &str) {
-        match text.trim().split_once(' ') {
-            Some((cmd, prompt)) => (cmd, prompt.trim()),
-            None => (text.trim(), ""),
-        }
-    }
-
     fn get_mime_type(file_name: &str) -> &'static str {
-        match file_name.split('.').last() {
+        match file_name.split('.').next_back() {
             Some("png") => "image/png",
             Some("jpg") | Some("jpeg") => "image/jpeg",
             Some("gif") => "image/gif",
@@ -335,12 +391,13 @@ impl ChatApp {
     }
 
     async fn check_rate_limit(
+        settings: &ChatConfig,
         rate_limiter: &Arc<DashMap<i64, Vec<Instant>>>,
         user_id: i64,
     ) -> Result<(), String> {
         let now = Instant::now();
         let mut requests = rate_limiter.entry(user_id).or_default();
-        requests.retain(|&t| now.duration_since(t).as_secs() < RATE_LIMIT_WINDOW);
+        requests.retain(|&t| now.duration_since(t).as_secs() < settings.rate_limit_window);
 
         debug!(
             "Rate limit check - user_id: {}, current requests in window: {}",
@@ -348,7 +405,7 @@ impl ChatApp {
             requests.len()
         );
 
-        if requests.len() >= RATE_LIMIT_MAX {
+        if requests.len() >= settings.rate_limit_max {
             debug!("Rate limit exceeded for user_id: {}", user_id);
             return Err("你问得太快了，休息一下吧~".to_string());
         }
@@ -435,30 +492,26 @@ impl ChatApp {
         // First, split the text into chunks if it's too long
         let text_chunks = Self::split_text_by_length(&text, MAX_MESSAGE_LENGTH);
 
+        let url_re = Regex::new(r"(https?://[\S]+\.(?:png|jpg|jpeg|gif|webp))").unwrap();
+
         for (index, chunk) in text_chunks.iter().enumerate() {
             // For each chunk, process it for images and send
-            let url_re = Regex::new(r"(https?://[\S]+\.(?:png|jpg|jpeg|gif|webp))").unwrap();
             let mut segments = Vec::new();
             let mut last_end = 0;
 
             for mat in url_re.find_iter(chunk) {
                 if mat.start() > last_end {
                     let text_part = &chunk[last_end..mat.start()];
-                    segments.push(Segment::Text {
-                        text: text_part.to_string(),
-                    });
+                    segments.push(Segment::text(text_part));
                 }
 
-                let url = mat.as_str().to_string();
-                segments.push(Segment::image(url));
+                segments.push(Segment::image(mat.as_str()));
 
                 last_end = mat.end();
             }
 
             if last_end < chunk.len() {
-                segments.push(Segment::Text {
-                    text: chunk[last_end..].to_string(),
-                });
+                segments.push(Segment::text(&chunk[last_end..]));
             }
 
             // Send the chunk
@@ -497,8 +550,11 @@ impl ChatApp {
 
         let response = context
             .client
-            .post(format!("{}/v1/chat/completions", context.base_url))
-            .bearer_auth(&context.token)
+            .post(format!(
+                "{}/v1/chat/completions",
+                context.settings.openai_base
+            ))
+            .bearer_auth(&context.settings.openai_token)
             .json(&req)
             .send()
             .await?;
@@ -540,3 +596,93 @@ impl ChatApp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::mock::{MockServer, group_message, http_fixture, private_message};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn ai_command_stops_propagation() {
+        let openai = http_fixture(json!({
+            "choices": [{ "message": { "role": "assistant", "content": "喵呜" } }],
+        }))
+        .await;
+        let cat = http_fixture(json!([{ "url": "https://cdn2.thecatapi.com/images/a.jpg" }])).await;
+        let switches = std::env::temp_dir().join("kanami-test-chat-switches.json");
+        _ = std::fs::remove_file(&switches);
+        let config = format!(
+            "token = \"\"\nowner = 1\nswitch_file = {:?}\n[apps.chat]\nopenai_base = \"{}\"\n[apps.cat]\napi = \"{}\"\n",
+            switches.display().to_string(),
+            openai,
+            cat
+        );
+        let server = MockServer::start(10007, &config).await;
+        server.push(group_message(400, 1, "!app enable chat"));
+        server.push(group_message(400, 1, "!app enable cat"));
+        server.wait_actions("send_group_msg", 2).await;
+        server.push(group_message(400, 2, "!ai 喵"));
+
+        let sent = server.wait_actions("send_group_msg", 3).await.remove(2);
+        assert_eq!(sent.params["message"][1]["data"]["text"], "喵呜");
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let images = server
+            .actions()
+            .into_iter()
+            .filter(|a| a.params["message"][0]["type"] == "image")
+            .count();
+        assert_eq!(images, 0);
+    }
+
+    #[tokio::test]
+    async fn switch_waits_for_model_name() {
+        let server = MockServer::start(10008, "token = \"\"\nowner = 1\n").await;
+        server.push(private_message(1, "!switch"));
+        server.wait_action("send_private_msg").await;
+        server.push(private_message(1, "gpt-test"));
+
+        let sent = server.wait_actions("send_private_msg", 2).await.remove(1);
+        assert_eq!(
+            sent.params["message"][1]["data"]["text"],
+            "已切换模型为: gpt-test"
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_model_does_not_hold_dispatch() {
+        use super::ChatApp;
+        use crate::{
+            application::{Application, Propagation},
+            protocol::event::MessageEvent,
+        };
+
+        // 只接受连接、从不应答的模型接口
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let openai = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let config = format!(
+            "token = \"\"\nowner = 1\n[apps.chat]\nopenai_base = \"{}\"\n",
+            openai
+        );
+        let _server = MockServer::start(10019, &config).await;
+        let app = ChatApp::new();
+        app.on_config_reload().await.unwrap();
+
+        let mut raw = private_message(2, "!ai 喵");
+        raw["time"] = 0.into();
+        raw["self_id"] = 10019.into();
+        raw["message_id"] = 0.into();
+        let event: MessageEvent = serde_json::from_value(raw).unwrap();
+        let propagation =
+            tokio::time::timeout(std::time::Duration::from_secs(1), app.on_message(&event))
+                .await
+                .expect("on_message waited for the model")
+                .unwrap();
+        assert_eq!(propagation, Propagation::Stop);
+    }
+}
@@ -0,0 +1,377 @@
+// 命令声明与解析
+//
+// 应用在 `Application::commands` 中声明命令，在 `on_event` 中调用 `route` 取得匹配的命令与参数，
+// 参数不合法时自动回复用法，权限不足时忽略
+use crate::{
+    application::permission::Permission,
+    protocol::{
+        event::MessageEvent,
+        message::{Message, Segment},
+    },
+};
+use anyhow::Result;
+use std::{collections::HashMap, collections::VecDeque, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    /// 整数
+    Int,
+    /// QQ 号，可以是 @ 或数字
+    User,
+    /// 一个词
+    Word,
+    /// 剩余的全部文本，只能作为最后一个参数
+    Rest,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+#[allow(unused)]
+impl Arg {
+    fn new(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub fn int(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Int)
+    }
+
+    pub fn user(name: &'static str) -> Self {
+        Self::new(name, ArgKind::User)
+    }
+
+    pub fn word(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Word)
+    }
+
+    pub fn rest(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Rest)
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.kind {
+            ArgKind::User => format!("@{}", self.name),
+            ArgKind::Rest => format!("{}...", self.name),
+            _ => self.name.to_string(),
+        };
+        if self.optional {
+            write!(f, "[{}]", name)
+        } else {
+            write!(f, "<{}>", name)
+        }
+    }
+}
+
+/// 命令声明，默认前缀为 `!`、所有人可用
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: String,
+    pub description: String,
+    pub prefix: &'static str,
+    pub aliases: Vec<String>,
+    pub args: Vec<Arg>,
+    pub permission: Permission,
+}
+
+#[allow(unused)]
+impl Command {
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            prefix: "!",
+            aliases: Vec::new(),
+            args: Vec::new(),
+            permission: Permission::Everyone,
+        }
+    }
+
+    pub fn prefix(mut self, prefix: &'static str) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn permission(mut self, permission: Permission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    /// 如 `!ban <@user> [minutes]`
+    pub fn usage(&self) -> String {
+        let mut usage = format!("{}{}", self.prefix, self.name);
+        for arg in &self.args {
+            usage.push_str(&format!(" {}", arg));
+        }
+        usage
+    }
+
+    fn matches(&self, head: &str) -> bool {
+        head.strip_prefix(self.prefix)
+            .is_some_and(|name| name == self.name || self.aliases.iter().any(|a| a == name))
+    }
+
+    /// 按声明解析参数，参数缺失、类型不符或多余时返回 `None`
+    fn parse(&self, input: &mut Input) -> Option<Args> {
+        let mut args = Args::default();
+        for arg in &self.args {
+            let value = match arg.kind {
+                ArgKind::Rest => Some(input.rest())
+                    .filter(|s| !s.is_empty())
+                    .map(ArgValue::Text),
+                kind => match (kind, input.next()) {
+                    (_, None) => None,
+                    (ArgKind::Int, Some(Token::Word(w))) => Some(ArgValue::Int(w.parse().ok()?)),
+                    (ArgKind::User, Some(Token::User(id))) => Some(ArgValue::User(id)),
+                    (ArgKind::User, Some(Token::Word(w))) => Some(ArgValue::User(w.parse().ok()?)),
+                    (ArgKind::Word, Some(Token::Word(w))) => Some(ArgValue::Text(w)),
+                    _ => return None,
+                },
+            };
+            match value {
+                Some(value) => _ = args.0.insert(arg.name, value),
+                None if arg.optional => {}
+                None => return None,
+            }
+        }
+        input.next().is_none().then_some(args)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    Int(i64),
+    User(i64),
+    Text(String),
+}
+
+/// 解析后的参数，可选参数缺省时取不到值
+#[derive(Debug, Default)]
+pub struct Args(HashMap<&'static str, ArgValue>);
+
+#[allow(unused)]
+impl Args {
+    pub fn int(&self, name: &str) -> Option<i64> {
+        match self.0.get(name)? {
+            ArgValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn user(&self, name: &str) -> Option<i64> {
+        match self.0.get(name)? {
+            ArgValue::User(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.0.get(name)? {
+            ArgValue::Text(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    User(i64),
+}
+
+#[derive(Debug)]
+enum Piece {
+    Text(String),
+    At(i64),
+}
+
+/// 按空白切分的消息，`@` 单独成词，非文本消息段被忽略
+struct Input(VecDeque<Piece>);
+
+impl Input {
+    /// 跳过开头的回复与对 bot 的 `@`
+    fn new(message: &Message, self_id: i64) -> Self {
+        let mut pieces = VecDeque::new();
+        for segment in message.segments() {
+            match segment {
+                Segment::Text { text } => pieces.push_back(Piece::Text(text.clone())),
+                Segment::At { qq } => {
+                    if let Ok(id) = qq.parse() {
+                        pieces.push_back(Piece::At(id));
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut input = Self(pieces);
+        while input.skip_space() && matches!(input.0.front(), Some(Piece::At(id)) if *id == self_id)
+        {
+            input.0.pop_front();
+        }
+        input
+    }
+
+    /// 去掉开头的空白，返回是否还有内容
+    fn skip_space(&mut self) -> bool {
+        while let Some(Piece::Text(text)) = self.0.front_mut() {
+            let trimmed = text.trim_start();
+            if trimmed.is_empty() {
+                self.0.pop_front();
+            } else {
+                *text = trimmed.to_string();
+                break;
+            }
+        }
+        !self.0.is_empty()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        if !self.skip_space() {
+            return None;
+        }
+        match self.0.pop_front()? {
+            Piece::At(id) => Some(Token::User(id)),
+            Piece::Text(text) => match text.split_once(char::is_whitespace) {
+                Some((word, rest)) => {
+                    self.0.push_front(Piece::Text(rest.to_string()));
+                    Some(Token::Word(word.to_string()))
+                }
+                None => Some(Token::Word(text)),
+            },
+        }
+    }
+
+    /// 剩余的全部内容，`@` 还原为 `@QQ 号`
+    fn rest(&mut self) -> String {
+        let mut rest = String::new();
+        for piece in self.0.drain(..) {
+            match piece {
+                Piece::Text(text) => rest.push_str(&text),
+                Piece::At(id) => rest.push_str(&format!("@{}", id)),
+            }
+        }
+        rest.trim().to_string()
+    }
+}
+
+/// 匹配事件对应的命令并解析参数
+///
+/// 参数不合法时回复用法并返回 `None`，无前缀的命令直接忽略
+pub async fn route<'a>(
+    commands: &'a [Command],
+    event: &MessageEvent,
+) -> Result<Option<(&'a Command, Args)>> {
+    let mut input = Input::new(event.message(), event.self_id());
+    let Some(Token::Word(head)) = input.next() else {
+        return Ok(None);
+    };
+    let Some(command) = commands.iter().find(|c| c.matches(&head)) else {
+        return Ok(None);
+    };
+    if Permission::of(event) < command.permission {
+        log::debug!(
+            "command {} denied for user {}",
+            command.name,
+            event.user_id()
+        );
+        return Ok(None);
+    }
+    match command.parse(&mut input) {
+        Some(args) => Ok(Some((command, args))),
+        // 无前缀的命令与普通聊天难以区分，不回复用法
+        None if command.prefix.is_empty() => Ok(None),
+        None => {
+            let usage = format!("用法: {}\n{}", command.usage(), command.description);
+            event.reply(usage, true).await?;
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(segments: Vec<Segment>) -> Input {
+        Input::new(&Message::from(segments), 10)
+    }
+
+    fn text(text: &str) -> Segment {
+        Segment::Text {
+            text: text.to_string(),
+        }
+    }
+
+    fn at(qq: i64) -> Segment {
+        Segment::At { qq: qq.to_string() }
+    }
+
+    #[test]
+    fn parse_typed_args() {
+        let command = Command::new("ban", "禁言")
+            .arg(Arg::user("user"))
+            .arg(Arg::int("minutes").optional());
+        let mut input = input(vec![at(10), text(" !ban "), at(42), text(" 30")]);
+        assert_eq!(input.next(), Some(Token::Word("!ban".to_string())));
+        let args = command.parse(&mut input).unwrap();
+        assert_eq!(args.user("user"), Some(42));
+        assert_eq!(args.int("minutes"), Some(30));
+        assert_eq!(command.usage(), "!ban <@user> [minutes]");
+    }
+
+    #[test]
+    fn reject_bad_args() {
+        let command = Command::new("ban", "禁言").arg(Arg::user("user"));
+        let mut missing = input(vec![text("!ban")]);
+        missing.next();
+        assert!(command.parse(&mut missing).is_none());
+        let mut extra = input(vec![text("!ban 42 43")]);
+        extra.next();
+        assert!(command.parse(&mut extra).is_none());
+        let mut typo = input(vec![text("!ban abc")]);
+        typo.next();
+        assert!(command.parse(&mut typo).is_none());
+    }
+
+    #[test]
+    fn rest_keeps_line_breaks() {
+        let command = Command::new("ai", "对话").arg(Arg::rest("prompt").optional());
+        let mut input = input(vec![text("!ai  hello\nworld "), at(7)]);
+        input.next();
+        let args = command.parse(&mut input).unwrap();
+        assert_eq!(args.text("prompt"), Some("hello\nworld @7"));
+    }
+
+    #[test]
+    fn match_alias() {
+        let command = Command::new("help", "帮助").alias("帮助");
+        assert!(command.matches("!help"));
+        assert!(command.matches("!帮助"));
+        assert!(!command.matches("help"));
+    }
+}
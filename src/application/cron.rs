@@ -1,22 +1,53 @@
-use std::sync::Arc;
-
 use anyhow::Result;
 use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::Mutex;
 use tokio_cron_scheduler::{Job, JobScheduler};
 
 use crate::{
     application::Application,
     config,
-    protocol::{event::Event, get_bot},
+    protocol::{event::EventKind, get_bot, get_bot_by_id},
 };
 
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CronConfig {
+    jobs: Vec<CronJob>,
+}
+
+/// 定时消息，`group_id` 与 `user_id` 二选一，`self_id` 缺省时由默认账号发送
+#[derive(Deserialize, Clone)]
+struct CronJob {
+    schedule: String,
+    self_id: Option<i64>,
+    group_id: Option<i64>,
+    user_id: Option<i64>,
+    message: String,
+}
+
 pub struct CronApp {
-    sched: Option<JobScheduler>,
+    sched: Mutex<Option<JobScheduler>>,
 }
 
-async fn send_prompt(content: &str) -> Result<()> {
-    let bot = get_bot().await;
-    bot.send_group_message(config::MAIN_GROUP, content).await?;
+async fn send_prompt(job: &CronJob) -> Result<()> {
+    let bot = match job.self_id {
+        Some(self_id) => get_bot_by_id(self_id),
+        None => get_bot().await,
+    };
+    match (job.group_id, job.user_id) {
+        (Some(group_id), _) => {
+            _ = bot
+                .send_group_message(group_id, job.message.as_str())
+                .await?
+        }
+        (None, Some(user_id)) => {
+            _ = bot
+                .send_private_message(user_id, job.message.as_str())
+                .await?
+        }
+        (None, None) => log::warn!("cron job <{}> has no target", job.schedule),
+    }
     Ok(())
 }
 
@@ -26,55 +57,62 @@ impl Application for CronApp {
         "cron"
     }
 
-    async fn on_load(&mut self) -> Result<()> {
-        let sched = JobScheduler::new().await?;
-
-        sched
-            .add(Job::new_async("0 0 0 * * 5", |_uuid, _l| {
-                Box::pin(async move {
-                    if let Err(e) = send_prompt("周五啦！").await {
-                        log::error!("failed to send prompt: {}", e);
-                    }
-                })
-            })?)
-            .await?;
-        sched
-            .add(Job::new_async("0 0 0 * * 1", |_uuid, _l| {
-                Box::pin(async move {
-                    if let Err(e) = send_prompt("周一啦！").await {
-                        log::error!("failed to send prompt: {}", e);
-                    }
-                })
-            })?)
-            .await?;
-
-        sched
-            .add(Job::new_async("0 0 13 * * *", |_uuid, _l| {
-                Box::pin(async move {
-                    if let Err(e) = get_bot()
-                        .await
-                        .send_private_message(config::USER_1, "晚上好！今天记得打卡哦~")
-                        .await
-                    {
-                        log::error!("failed to send prompt: {}", e);
-                    }
-                })
-            })?)
-            .await?;
+    /// 定时任务不处理事件
+    fn subscriptions(&self) -> &[EventKind] {
+        &[]
+    }
 
-        sched.start().await?;
-        self.sched = Some(sched);
+    async fn on_startup(&self) -> Result<()> {
+        self.schedule().await?;
         log::info!("app <{}> loaded", self.name());
         Ok(())
     }
 
-    async fn on_event(&mut self, _event: Arc<Event>) -> Result<()> {
+    async fn on_config_reload(&self) -> Result<()> {
+        self.schedule().await
+    }
+
+    async fn on_shutdown(&self) -> Result<()> {
+        if let Some(mut sched) = self.sched.lock().await.take() {
+            sched.shutdown().await?;
+        }
         Ok(())
     }
 }
 
 impl CronApp {
     pub fn new() -> Self {
-        Self { sched: None }
+        Self {
+            sched: Mutex::new(None),
+        }
+    }
+
+    /// 按当前配置重建定时任务，旧的调度器会被关闭
+    async fn schedule(&self) -> Result<()> {
+        let mut current = self.sched.lock().await;
+        if let Some(mut sched) = current.take() {
+            sched.shutdown().await?;
+        }
+
+        let settings: CronConfig = config::get().app("cron")?;
+        let sched = JobScheduler::new().await?;
+
+        for job in settings.jobs {
+            let schedule = job.schedule.clone();
+            sched
+                .add(Job::new_async(schedule.as_str(), move |_uuid, _l| {
+                    let job = job.clone();
+                    Box::pin(async move {
+                        if let Err(e) = send_prompt(&job).await {
+                            log::error!("failed to send prompt: {}", e);
+                        }
+                    })
+                })?)
+                .await?;
+        }
+
+        sched.start().await?;
+        *current = Some(sched);
+        Ok(())
     }
 }
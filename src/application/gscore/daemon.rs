@@ -1,7 +1,6 @@
-use crate::application::gscore::model::*;
-use crate::config;
-use crate::protocol::get_bot;
+use crate::application::gscore::{model::*, settings};
 use crate::protocol::message::Segment;
+use crate::protocol::{get_bot, get_bot_by_id};
 use anyhow::{Result, anyhow};
 use futures_util::{SinkExt, StreamExt};
 use std::time::Duration;
@@ -38,14 +37,14 @@ pub async fn gscore_loop(mut receiver: mpsc::Receiver<MessageReceive>) {
 }
 
 async fn connect_gscore() -> Result<WsStream> {
-    log::info!("Connecting to GSCore at {}", config::GSCORE_ENDPOINT);
+    let endpoint = settings().endpoint.clone();
+    log::info!("Connecting to GSCore at {}", endpoint);
 
     let mut ws_config = WebSocketConfig::default();
     ws_config.max_message_size = Some(64 * 1024 * 1024);
     ws_config.max_frame_size = Some(16 * 1024 * 1024);
 
-    let (ws, _) =
-        connect_async_with_config(config::GSCORE_ENDPOINT, Some(ws_config), false).await?;
+    let (ws, _) = connect_async_with_config(endpoint, Some(ws_config), false).await?;
 
     log::info!("GSCore WebSocket connection established");
     Ok(ws)
@@ -171,17 +170,19 @@ async fn process_message_send(mut send: MessageSend) -> Result<()> {
         .ok_or_else(|| anyhow!("no target_id"))?
         .parse()?;
 
+    let settings = settings();
     let forwards = content
         .iter()
         .filter_map(|x| {
             if let GSCoreMessage::Node(x) = x {
                 Some(
                     x.iter()
-                        .map(|e| Segment::Node {
-                            id: None,
-                            user_id: Some(config::GSCORE_NODE_SENDER_ID.to_string()),
-                            nickname: Some(config::GSCORE_NODE_SENDER_NICKNAME.to_string()),
-                            content: Some(Segment::from(e).into()),
+                        .map(|e| {
+                            Segment::custom_node(
+                                &settings.node_sender_id,
+                                settings.node_sender_nickname.clone(),
+                                Segment::from(e),
+                            )
                         })
                         .collect::<Vec<Segment>>(),
                 )
@@ -192,15 +193,29 @@ async fn process_message_send(mut send: MessageSend) -> Result<()> {
         .flatten()
         .collect::<Vec<Segment>>();
 
+    // 由收到消息的账号回复
+    let bot = match send.bot_self_id.parse() {
+        Ok(self_id) => get_bot_by_id(self_id),
+        Err(_) => get_bot().await,
+    };
     if !forwards.is_empty() {
-        get_bot()
-            .await
-            .send_forward_msg(Some(target_type), Some(target), Some(target), forwards)
+        #[cfg(feature = "napcat")]
+        bot.send_forward_msg(Some(target_type), Some(target), Some(target), forwards)
             .await?;
+        // 不支持合并转发时逐条发送各节点的内容
+        #[cfg(not(feature = "napcat"))]
+        for node in forwards {
+            if let Segment::Node {
+                content: Some(content),
+                ..
+            } = node
+            {
+                bot.send_message(Some(target_type), Some(target), Some(target), content)
+                    .await?;
+            }
+        }
     } else {
-        get_bot()
-            .await
-            .send_message(Some(target_type), Some(target), Some(target), content)
+        bot.send_message(Some(target_type), Some(target), Some(target), content)
             .await?;
     }
     Ok(())
@@ -1,80 +1,122 @@
 // https://docs.sayu-bot.com/CodeAdapter/Protocol.html
 
 use crate::{
+    application::Propagation,
     config,
-    protocol::event::{Event, MessageEvent},
+    protocol::event::{EventKind, MessageEvent},
 };
 use anyhow::Result;
 use async_trait::async_trait;
-use std::sync::Arc;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::sync::{Arc, Mutex, RwLock};
 use tokio::sync::mpsc;
 
 mod daemon;
 mod model;
 use model::*;
 
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GSCoreConfig {
+    pub endpoint: String,
+    pub bot_id: String,
+    pub node_sender_id: String,
+    pub node_sender_nickname: String,
+}
+
+impl Default for GSCoreConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "ws://127.0.0.1:8765/ws/kanami".to_string(),
+            bot_id: "Kanami".to_string(),
+            node_sender_id: "1145141919810".to_string(),
+            node_sender_nickname: "Kanami".to_string(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref SETTINGS: RwLock<Arc<GSCoreConfig>> = RwLock::new(Arc::default());
+}
+
+/// daemon 与消息转换共用的配置
+fn settings() -> Arc<GSCoreConfig> {
+    SETTINGS.read().unwrap().clone()
+}
+
 pub struct GSCoreAdapter {
-    sender: Option<mpsc::Sender<MessageReceive>>,
-    connection_starting: bool,
+    sender: Mutex<Option<mpsc::Sender<MessageReceive>>>,
 }
 
 #[async_trait]
 impl super::Application for GSCoreAdapter {
     fn name(&self) -> &str {
-        "gscore adapter"
+        "gscore"
+    }
+
+    fn subscriptions(&self) -> &[EventKind] {
+        &[EventKind::Message]
     }
 
-    async fn on_load(&mut self) -> Result<()> {
+    fn default_enabled(&self) -> bool {
+        false
+    }
+
+    /// 按收到的顺序转发给 GSCore
+    fn sequential(&self) -> bool {
+        true
+    }
+
+    async fn on_startup(&self) -> Result<()> {
+        self.on_config_reload().await?;
         log::info!("app <{}> loaded", self.name());
-        self.start_gscore_connection().await?;
+        self.start_gscore_connection();
         Ok(())
     }
 
-    async fn on_event(&mut self, event: Arc<Event>) -> Result<()> {
-        if let Event::MessageEvent(msg_event) = event.as_ref() {
-            if let MessageEvent::Group(e) = msg_event {
-                if !config::GSCORE_ENABLED_GROUPS.contains(&e.group_id) {
-                    return Ok(());
-                }
-            }
-            if let Some(sender) = &self.sender {
-                let message_receive = msg_event.into();
-                if let Err(e) = sender.send(message_receive).await {
-                    log::info!("GSCore handler unavailable ({}), restarting connection...", e);
-                    // 重新启动连接
-                    self.sender = None;
-                    if let Err(restart_err) = self.start_gscore_connection().await {
-                        log::warn!("Failed to restart GSCore connection: {}", restart_err);
-                    }
-                }
+    /// `endpoint` 在下次重连 GSCore 时生效
+    async fn on_config_reload(&self) -> Result<()> {
+        *SETTINGS.write().unwrap() = Arc::new(config::get().app("gscore")?);
+        Ok(())
+    }
+
+    async fn on_message(&self, event: &MessageEvent) -> Result<Propagation> {
+        let sender = self.sender.lock().unwrap().clone();
+        if let Some(sender) = sender {
+            let message_receive = event.into();
+            if let Err(e) = sender.send(message_receive).await {
+                log::info!(
+                    "GSCore handler unavailable ({}), restarting connection...",
+                    e
+                );
+                // 重新启动连接
+                *self.sender.lock().unwrap() = None;
+                self.start_gscore_connection();
             }
         }
-        Ok(())
+        Ok(Propagation::Continue)
     }
 }
 
 impl GSCoreAdapter {
     pub fn new() -> Self {
-        Self { 
-            sender: None,
-            connection_starting: false,
+        Self {
+            sender: Mutex::new(None),
         }
     }
 
-    async fn start_gscore_connection(&mut self) -> Result<()> {
-        if self.sender.is_some() || self.connection_starting {
-            return Ok(()); // Already connected or connecting
+    fn start_gscore_connection(&self) {
+        let mut sender = self.sender.lock().unwrap();
+        if sender.is_some() {
+            return; // Already connected
         }
 
-        self.connection_starting = true;
         let (tx, rx) = mpsc::channel(100); // Increase buffer size
-        self.sender = Some(tx);
-        self.connection_starting = false;
+        *sender = Some(tx);
 
         tokio::spawn(async move {
             daemon::gscore_loop(rx).await;
         });
-
-        Ok(())
     }
 }
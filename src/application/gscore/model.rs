@@ -1,5 +1,6 @@
+use super::settings;
 use crate::{
-    config,
+    application::permission::Permission,
     protocol::{
         event::{GroupRole, MessageEvent},
         message::{Message, Segment},
@@ -74,11 +75,22 @@ pub struct MessageSend {
     pub content: Option<Vec<GSCoreMessage>>,
 }
 
+/// GSCore 的权限等级，0 为主人、1 为超管、2 为群主、3 为管理员、6 为普通用户
+fn user_pm(event: &MessageEvent, role: Option<&GroupRole>) -> i32 {
+    match (Permission::of(event), role) {
+        (Permission::Owner, _) => 0,
+        (Permission::Superuser, _) => 1,
+        (_, Some(GroupRole::Owner)) => 2,
+        (Permission::GroupAdmin, _) => 3,
+        _ => 6,
+    }
+}
+
 impl From<&MessageEvent> for MessageReceive {
     fn from(value: &MessageEvent) -> Self {
         match value {
             MessageEvent::Group(event) => Self {
-                bot_id: config::GSCORE_BOTID.to_string(),
+                bot_id: settings().bot_id.clone(),
                 bot_self_id: event.base.self_id.to_string(),
                 msg_id: event.message_id.to_string(),
                 user_type: TargetType::Group,
@@ -91,15 +103,11 @@ impl From<&MessageEvent> for MessageReceive {
                         event.user_id
                     ),
                 },
-                user_pm: match event.sender.role {
-                    Some(GroupRole::Owner) => 2,
-                    Some(GroupRole::Admin) => 3,
-                    _ => 6,
-                },
+                user_pm: user_pm(value, event.sender.role.as_ref()),
                 content: (&event.message).into(),
             },
             MessageEvent::Private(event) => Self {
-                bot_id: config::GSCORE_BOTID.to_string(),
+                bot_id: settings().bot_id.clone(),
                 bot_self_id: event.base.self_id.to_string(),
                 msg_id: event.message_id.to_string(),
                 user_type: TargetType::Direct,
@@ -112,7 +120,7 @@ impl From<&MessageEvent> for MessageReceive {
                         event.user_id
                     ),
                 },
-                user_pm: 6,
+                user_pm: user_pm(value, None),
                 content: (&event.message).into(),
             },
         }
@@ -123,14 +131,7 @@ impl From<&Segment> for GSCoreMessage {
     fn from(value: &Segment) -> Self {
         match value {
             Segment::Text { text } => GSCoreMessage::Text(text.clone()),
-            Segment::Image {
-                file,
-                catagary: _,
-                url: _,
-                cache: _,
-                proxy: _,
-                timeout: _,
-            } => GSCoreMessage::Image(file.clone()),
+            Segment::Image { file, .. } => GSCoreMessage::Image(file.to_string()),
             Segment::At { qq } => GSCoreMessage::At(qq.clone()),
             Segment::Reply { id } => GSCoreMessage::Reply(id.clone()),
             _ => GSCoreMessage::Text(format!("<unsupp: {:?}>", value)),
@@ -141,32 +142,17 @@ impl From<&Segment> for GSCoreMessage {
 impl From<&GSCoreMessage> for Segment {
     fn from(value: &GSCoreMessage) -> Self {
         match value {
-            GSCoreMessage::Text(text) => Segment::Text { text: text.clone() },
-            GSCoreMessage::Markdown(text) => Segment::Text { text: text.clone() },
-            GSCoreMessage::Image(url) => Segment::Image {
-                file: url.clone(),
-                catagary: None,
-                url: None,
-                cache: None,
-                proxy: None,
-                timeout: None,
-            },
-            GSCoreMessage::At(qq) => Segment::At { qq: qq.clone() },
-            GSCoreMessage::Reply(id) => Segment::Reply { id: id.clone() },
-            GSCoreMessage::Node(node) => Segment::Node {
-                id: None,
-                user_id: Some(config::GSCORE_NODE_SENDER_ID.to_string()),
-                nickname: Some(config::GSCORE_NODE_SENDER_NICKNAME.to_string()),
-                content: Some(
-                    node.iter()
-                        .map(|seg| seg.into())
-                        .collect::<Vec<Segment>>()
-                        .into(),
-                ),
-            },
-            _ => Segment::Text {
-                text: format!("<unsupp: {:?}>", value),
-            },
+            GSCoreMessage::Text(text) => Segment::text(text),
+            GSCoreMessage::Markdown(text) => Segment::text(text),
+            GSCoreMessage::Image(url) => Segment::image(url.clone()),
+            GSCoreMessage::At(qq) => Segment::at(qq),
+            GSCoreMessage::Reply(id) => Segment::reply(id),
+            GSCoreMessage::Node(node) => Segment::custom_node(
+                &settings().node_sender_id,
+                settings().node_sender_nickname.clone(),
+                node.iter().map(Segment::from).collect::<Message>(),
+            ),
+            _ => Segment::text(format!("<unsupp: {:?}>", value)),
         }
     }
 }
@@ -174,32 +160,17 @@ impl From<&GSCoreMessage> for Segment {
 impl From<GSCoreMessage> for Segment {
     fn from(value: GSCoreMessage) -> Self {
         match value {
-            GSCoreMessage::Text(text) => Segment::Text { text },
-            GSCoreMessage::Markdown(text) => Segment::Text { text },
-            GSCoreMessage::Image(url) => Segment::Image {
-                file: url,
-                catagary: None,
-                url: None,
-                cache: None,
-                proxy: None,
-                timeout: None,
-            },
-            GSCoreMessage::At(qq) => Segment::At { qq },
-            GSCoreMessage::Reply(id) => Segment::Reply { id },
-            GSCoreMessage::Node(node) => Segment::Node {
-                id: None,
-                user_id: Some(config::GSCORE_NODE_SENDER_ID.to_string()),
-                nickname: Some(config::GSCORE_NODE_SENDER_NICKNAME.to_string()),
-                content: Some(
-                    node.into_iter()
-                        .map(|seg| seg.into())
-                        .collect::<Vec<Segment>>()
-                        .into(),
-                ),
-            },
-            _ => Segment::Text {
-                text: format!("<unsupp: {:?}>", value),
-            },
+            GSCoreMessage::Text(text) => Segment::text(text),
+            GSCoreMessage::Markdown(text) => Segment::text(text),
+            GSCoreMessage::Image(url) => Segment::image(url),
+            GSCoreMessage::At(qq) => Segment::at(qq),
+            GSCoreMessage::Reply(id) => Segment::reply(id),
+            GSCoreMessage::Node(node) => Segment::custom_node(
+                &settings().node_sender_id,
+                settings().node_sender_nickname.clone(),
+                node.into_iter().map(Segment::from).collect::<Message>(),
+            ),
+            _ => Segment::text(format!("<unsupp: {:?}>", value)),
         }
     }
 }
@@ -207,11 +178,11 @@ impl From<GSCoreMessage> for Segment {
 impl From<&GSCoreMessageWithoutNode> for Segment {
     fn from(value: &GSCoreMessageWithoutNode) -> Self {
         match value {
-            GSCoreMessageWithoutNode::Text(text) => Segment::Text { text: text.clone() },
-            GSCoreMessageWithoutNode::Markdown(text) => Segment::Text { text: text.clone() },
+            GSCoreMessageWithoutNode::Text(text) => Segment::text(text),
+            GSCoreMessageWithoutNode::Markdown(text) => Segment::text(text),
             GSCoreMessageWithoutNode::Image(url) => Segment::image(url.clone()),
-            GSCoreMessageWithoutNode::At(qq) => Segment::At { qq: qq.clone() },
-            GSCoreMessageWithoutNode::Reply(id) => Segment::Reply { id: id.clone() },
+            GSCoreMessageWithoutNode::At(qq) => Segment::at(qq),
+            GSCoreMessageWithoutNode::Reply(id) => Segment::reply(id),
         }
     }
 }
@@ -219,18 +190,11 @@ impl From<&GSCoreMessageWithoutNode> for Segment {
 impl From<GSCoreMessageWithoutNode> for Segment {
     fn from(value: GSCoreMessageWithoutNode) -> Self {
         match value {
-            GSCoreMessageWithoutNode::Text(text) => Segment::Text { text },
-            GSCoreMessageWithoutNode::Markdown(text) => Segment::Text { text },
-            GSCoreMessageWithoutNode::Image(url) => Segment::Image {
-                file: url,
-                catagary: None,
-                url: None,
-                cache: None,
-                proxy: None,
-                timeout: None,
-            },
-            GSCoreMessageWithoutNode::At(qq) => Segment::At { qq },
-            GSCoreMessageWithoutNode::Reply(id) => Segment::Reply { id },
+            GSCoreMessageWithoutNode::Text(text) => Segment::text(text),
+            GSCoreMessageWithoutNode::Markdown(text) => Segment::text(text),
+            GSCoreMessageWithoutNode::Image(url) => Segment::image(url),
+            GSCoreMessageWithoutNode::At(qq) => Segment::at(qq),
+            GSCoreMessageWithoutNode::Reply(id) => Segment::reply(id),
         }
     }
 }
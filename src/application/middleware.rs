@@ -0,0 +1,213 @@
+// 事件分发前的中间件链，每个事件只经过一次，任一中间件拒绝即丢弃
+//
+// 黑名单、静默时段等配置位于 `[filter]`，每次处理时读取，重新加载配置后立即生效
+use crate::{
+    application::permission,
+    config,
+    protocol::event::{Event, MessageEvent},
+};
+use lazy_static::lazy_static;
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+};
+
+/// 去重时记住的最近消息数
+const DEDUP_CAPACITY: usize = 1024;
+
+pub trait Middleware: Send + Sync {
+    fn name(&self) -> &str;
+    /// 返回 `false` 时丢弃事件，之后的中间件与应用都不会收到
+    fn accept(&self, event: &Event) -> bool;
+}
+
+lazy_static! {
+    static ref CHAIN: Vec<Box<dyn Middleware>> = vec![
+        Box::new(IgnoreSelf),
+        Box::new(Blacklist),
+        Box::new(Mute),
+        Box::new(Dedup::new(DEDUP_CAPACITY)),
+    ];
+}
+
+/// 依次执行中间件链，返回事件是否应当分发
+pub fn accept(event: &Event) -> bool {
+    CHAIN.iter().all(|middleware| {
+        let accepted = middleware.accept(event);
+        if !accepted {
+            log::debug!("event dropped by <{}>", middleware.name());
+        }
+        accepted
+    })
+}
+
+/// 忽略账号自己发出的消息
+struct IgnoreSelf;
+
+impl Middleware for IgnoreSelf {
+    fn name(&self) -> &str {
+        "ignore_self"
+    }
+
+    fn accept(&self, event: &Event) -> bool {
+        match event {
+            Event::MessageEvent(e) => {
+                !config::get().filter.ignore_self || e.user_id() != e.self_id()
+            }
+            _ => true,
+        }
+    }
+}
+
+/// 忽略黑名单用户与群的消息
+struct Blacklist;
+
+impl Middleware for Blacklist {
+    fn name(&self) -> &str {
+        "blacklist"
+    }
+
+    fn accept(&self, event: &Event) -> bool {
+        let Event::MessageEvent(e) = event else {
+            return true;
+        };
+        let filter = &config::get().filter;
+        if filter.blacklist_users.contains(&e.user_id()) {
+            return false;
+        }
+        match e {
+            MessageEvent::Group(e) => !filter.blacklist_groups.contains(&e.group_id),
+            MessageEvent::Private(_) => true,
+        }
+    }
+}
+
+/// 静默时段内只处理主人的消息
+struct Mute;
+
+impl Middleware for Mute {
+    fn name(&self) -> &str {
+        "mute"
+    }
+
+    fn accept(&self, event: &Event) -> bool {
+        let Event::MessageEvent(e) = event else {
+            return true;
+        };
+        let now = chrono::Local::now().time();
+        let muted = config::get().filter.mute.iter().any(|w| w.contains(now));
+        !muted || permission::is_owner(e.user_id())
+    }
+}
+
+/// `(self_id, message_id)`
+type MessageKey = (i64, i32);
+
+/// 丢弃重复的 `message_id`，重连后实现重新推送的消息只处理一次
+struct Dedup {
+    capacity: usize,
+    seen: Mutex<(HashSet<MessageKey>, VecDeque<MessageKey>)>,
+}
+
+impl Dedup {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seen: Mutex::new((HashSet::new(), VecDeque::new())),
+        }
+    }
+}
+
+impl Middleware for Dedup {
+    fn name(&self) -> &str {
+        "dedup"
+    }
+
+    fn accept(&self, event: &Event) -> bool {
+        let Event::MessageEvent(e) = event else {
+            return true;
+        };
+        let key = (e.self_id(), e.message_id());
+        let mut seen = self.seen.lock().unwrap();
+        let (set, order) = &mut *seen;
+        if !set.insert(key) {
+            return false;
+        }
+        order.push_back(key);
+        if order.len() > self.capacity
+            && let Some(old) = order.pop_front()
+        {
+            set.remove(&old);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dedup, Middleware};
+    use crate::{
+        config::MuteWindow,
+        protocol::{
+            event::Event,
+            mock::{MockServer, group_message, private_message},
+        },
+    };
+    use chrono::NaiveTime;
+
+    fn event(message_id: i32) -> Event {
+        let mut raw = group_message(1, 2, "hi");
+        raw["message_id"] = message_id.into();
+        raw["time"] = 0.into();
+        raw["self_id"] = 10.into();
+        serde_json::from_value(raw).unwrap()
+    }
+
+    #[test]
+    fn dedup_forgets_oldest() {
+        let dedup = Dedup::new(2);
+        assert!(dedup.accept(&event(1)));
+        assert!(!dedup.accept(&event(1)));
+        assert!(dedup.accept(&event(2)));
+        assert!(dedup.accept(&event(3)));
+        assert!(dedup.accept(&event(1)));
+        assert!(!dedup.accept(&event(3)));
+    }
+
+    #[test]
+    fn mute_window_wraps_midnight() {
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let night = MuteWindow::try_from("23:00-07:00".to_string()).unwrap();
+        assert!(night.contains(at(23, 30)));
+        assert!(night.contains(at(6, 59)));
+        assert!(!night.contains(at(7, 0)));
+        let noon = MuteWindow::try_from("12:00-13:00".to_string()).unwrap();
+        assert!(noon.contains(at(12, 0)));
+        assert!(!noon.contains(at(13, 0)));
+        assert!(MuteWindow::try_from("25:00-1".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn filtered_messages_are_not_dispatched() {
+        let config = "token = \"\"\nowner = 1\n[filter]\nblacklist_users = [2]\n";
+        let server = MockServer::start(10006, config).await;
+        server.push(private_message(2, "!help"));
+        server.push(private_message(10006, "!help"));
+        let mut repeated = private_message(3, "!help");
+        repeated["message_id"] = 42.into();
+        server.push(repeated.clone());
+        server.push(repeated);
+        server.push(private_message(4, "!help"));
+
+        server.wait_actions("send_private_msg", 2).await;
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let targets: Vec<_> = server
+            .actions()
+            .into_iter()
+            .filter(|a| a.action == "send_private_msg")
+            .map(|a| a.params["user_id"].clone())
+            .collect();
+        assert_eq!(targets.len(), 2);
+        assert!(targets.contains(&3.into()) && targets.contains(&4.into()));
+    }
+}
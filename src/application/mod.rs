@@ -1,17 +1,24 @@
-use std::sync::Arc;
+use std::{ops::Deref, sync::Arc};
 
 use crate::{
     application::{
-        builtin::BuiltinApp, cat::CatApp, chat::ChatApp, cron::CronApp, gscore::GSCoreAdapter,
-        muri::MuriApp, ping::PingApp,
+        builtin::BuiltinApp, cat::CatApp, chat::ChatApp, command::Command, cron::CronApp,
+        gscore::GSCoreAdapter, muri::MuriApp, ping::PingApp,
     },
     config,
-    protocol::event::Event,
+    protocol::{
+        bots,
+        event::{
+            Event, EventKind, GroupMessage, MessageEvent, MetaEvent, Notice, PrivateMessage,
+            Request,
+        },
+    },
 };
 use anyhow::Result;
 use async_trait::async_trait;
+use futures_util::future::{BoxFuture, join_all};
 use lazy_static::lazy_static;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OnceCell};
 
 mod builtin;
 mod cat;
@@ -19,36 +26,306 @@ mod chat;
 mod gscore;
 mod muri;
 mod ping;
+/// 编写应用的示例，不注册到 `APPS`，只在测试中编译以保证示例能通过检查
+#[cfg(test)]
+mod template;
 
+pub mod command;
 pub mod cron;
+pub mod middleware;
+pub mod permission;
+pub mod session;
+pub mod switchboard;
+
+/// 应用的分发顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// 按数值从小到大依次处理，返回 `Propagation::Stop` 时之后的应用不再收到事件
+    Blocking(i32),
+    /// 阻塞应用处理完后并发处理，返回值不影响其他应用
+    Concurrent,
+}
+
+/// `on_event` 的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Propagation {
+    Continue,
+    /// 事件已被处理，停止传播
+    Stop,
+}
+
+/// 已注册的应用，顺序处理的应用额外持有一把锁
+pub struct AppEntry {
+    app: Box<dyn Application>,
+    sequential: Option<Mutex<()>>,
+}
+
+impl AppEntry {
+    pub fn subscribes(&self, kind: EventKind) -> bool {
+        self.app.subscriptions().contains(&kind)
+    }
 
-type AppType = Arc<Mutex<Box<dyn Application>>>;
+    /// 处理事件，顺序处理的应用需等待上一个事件处理完
+    pub async fn handle(&self, event: Arc<Event>) -> Result<Propagation> {
+        let _guard = match &self.sequential {
+            Some(lock) => Some(lock.lock().await),
+            None => None,
+        };
+        self.app.on_event(event).await
+    }
+}
+
+impl Deref for AppEntry {
+    type Target = dyn Application;
 
+    fn deref(&self) -> &Self::Target {
+        self.app.as_ref()
+    }
+}
+
+/// 应用的状态由自身以内部可变性管理，同一应用的事件默认并发处理
 #[async_trait]
 pub trait Application: Send + Sync {
     fn name(&self) -> &str;
-    async fn on_load(&mut self) -> Result<()> {
+    /// 应用声明的命令，由 `command::route` 匹配，`!help` 据此生成帮助
+    fn commands(&self) -> &[Command] {
+        &[]
+    }
+    /// 应用关心的事件类型，其余事件不会分发给应用
+    fn subscriptions(&self) -> &[EventKind] {
+        EventKind::ALL
+    }
+    fn priority(&self) -> Priority {
+        Priority::Concurrent
+    }
+    /// 为 `true` 时逐个处理事件，上一个事件处理完才收到下一个
+    fn sequential(&self) -> bool {
+        false
+    }
+    /// 群内未设置开关时是否启用，为 `false` 时需在群内 `!app enable` 开启
+    fn default_enabled(&self) -> bool {
+        true
+    }
+    /// 进程启动时调用一次，早于任何连接
+    async fn on_startup(&self) -> Result<()> {
         log::info!("app <{}> loaded", self.name());
         Ok(())
     }
-    async fn on_event(&mut self, event: Arc<Event>) -> Result<()>;
+    /// 进程退出前调用一次
+    async fn on_shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+    /// 账号连接并注册后调用，每次重连都会调用
+    async fn on_connect(&self, _self_id: i64) -> Result<()> {
+        Ok(())
+    }
+    /// 账号连接断开后调用
+    async fn on_disconnect(&self, _self_id: i64) -> Result<()> {
+        Ok(())
+    }
+    /// 配置重新加载后调用，应用在此重新读取自己的配置段
+    async fn on_config_reload(&self) -> Result<()> {
+        Ok(())
+    }
+    /// 按事件类型分发给下面的 `on_*`，需要完整事件时重写
+    async fn on_event(&self, event: Arc<Event>) -> Result<Propagation> {
+        match event.as_ref() {
+            Event::MessageEvent(e) => self.on_message(e).await,
+            Event::Notice(e) => self.on_notice(e).await,
+            Event::RequestEvent(e) => self.on_request(e).await,
+            Event::MetaEvent(e) => self.on_meta(e).await,
+            Event::Unknown { .. } => Ok(Propagation::Continue),
+        }
+    }
+    /// 群聊与私聊消息，默认分别交给 `on_group_message`、`on_private_message`
+    async fn on_message(&self, event: &MessageEvent) -> Result<Propagation> {
+        match event {
+            MessageEvent::Group(e) => self.on_group_message(e).await,
+            MessageEvent::Private(e) => self.on_private_message(e).await,
+        }
+    }
+    async fn on_group_message(&self, _event: &GroupMessage) -> Result<Propagation> {
+        Ok(Propagation::Continue)
+    }
+    async fn on_private_message(&self, _event: &PrivateMessage) -> Result<Propagation> {
+        Ok(Propagation::Continue)
+    }
+    async fn on_notice(&self, _event: &Notice) -> Result<Propagation> {
+        Ok(Propagation::Continue)
+    }
+    async fn on_request(&self, _event: &Request) -> Result<Propagation> {
+        Ok(Propagation::Continue)
+    }
+    async fn on_meta(&self, _event: &MetaEvent) -> Result<Propagation> {
+        Ok(Propagation::Continue)
+    }
 }
 
-fn create_app(app: Box<dyn Application>) -> AppType {
-    Arc::new(Mutex::new(app))
+fn create_app(app: Box<dyn Application>) -> AppEntry {
+    let sequential = app.sequential().then(|| Mutex::new(()));
+    AppEntry { app, sequential }
 }
 
 lazy_static! {
-    pub static ref APPS: Vec<AppType> = vec![
+    pub static ref APPS: Vec<AppEntry> = vec![
         create_app(Box::new(BuiltinApp::new())),
         create_app(Box::new(PingApp::new())),
         create_app(Box::new(GSCoreAdapter::new())),
         create_app(Box::new(CatApp::new())),
         create_app(Box::new(CronApp::new())),
-        create_app(Box::new(ChatApp::new(
-            config::OPENAI_TOKEN,
-            config::OPENAI_BASE
-        ))),
+        create_app(Box::new(ChatApp::new())),
         create_app(Box::new(MuriApp::new()))
     ];
+    /// 按优先级排列的应用，阻塞应用在前
+    pub static ref DISPATCH_ORDER: Vec<&'static AppEntry> = {
+        let mut apps: Vec<_> = APPS.iter().collect();
+        apps.sort_by_key(|app| app.priority());
+        apps
+    };
+}
+
+static STARTED: OnceCell<()> = OnceCell::const_new();
+
+/// 并发调用所有应用的 `hook` 并等待完成，出错时记录日志
+async fn run_hook<F>(hook: &str, f: F)
+where
+    F: Fn(&'static AppEntry) -> BoxFuture<'static, Result<()>>,
+{
+    join_all(APPS.iter().map(|app| async {
+        if let Err(e) = f(app).await {
+            log::warn!("app <{}> {} error: {}", app.name(), hook, e)
+        }
+    }))
+    .await;
+}
+
+/// 执行所有应用的 `on_startup`，只有首次调用生效
+pub async fn startup() {
+    STARTED
+        .get_or_init(|| run_hook("on_startup", |app| app.on_startup()))
+        .await;
+}
+
+/// 通知在线账号断开后执行所有应用的 `on_shutdown`
+pub async fn shutdown() {
+    for self_id in bots() {
+        disconnected(self_id).await;
+    }
+    run_hook("on_shutdown", |app| app.on_shutdown()).await;
+}
+
+pub async fn connected(self_id: i64) {
+    run_hook("on_connect", |app| app.on_connect(self_id)).await;
+}
+
+pub async fn disconnected(self_id: i64) {
+    run_hook("on_disconnect", |app| app.on_disconnect(self_id)).await;
+}
+
+/// 重新加载配置并通知所有应用，加载失败时保留旧配置
+pub async fn reload_config() {
+    if let Err(e) = config::load() {
+        log::warn!("config reload failed: {}", e);
+        return;
+    }
+    config_reloaded().await;
+}
+
+/// 等待所有应用的 `on_config_reload` 完成
+pub async fn config_reloaded() {
+    run_hook("on_config_reload", |app| app.on_config_reload()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Application, Propagation, create_app};
+    use crate::protocol::{
+        event::{Event, GroupMessage},
+        mock::{group_message, private_message},
+    };
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use futures_util::future::join_all;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    /// 记录同时处理的事件数的峰值
+    #[derive(Default)]
+    struct Slow {
+        sequential: bool,
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Application for Slow {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn sequential(&self) -> bool {
+            self.sequential
+        }
+
+        async fn on_event(&self, _event: Arc<Event>) -> Result<Propagation> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(Propagation::Continue)
+        }
+    }
+
+    fn event(mut raw: serde_json::Value) -> Arc<Event> {
+        raw["time"] = 0.into();
+        raw["self_id"] = 1.into();
+        raw["message_id"] = 1.into();
+        Arc::new(serde_json::from_value(raw).unwrap())
+    }
+
+    async fn peak(sequential: bool) -> usize {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let app = create_app(Box::new(Slow {
+            sequential,
+            peak: peak.clone(),
+            ..Default::default()
+        }));
+        let event = event(private_message(1, "hi"));
+        join_all((0..3).map(|_| app.handle(event.clone()))).await;
+        peak.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn events_run_concurrently_unless_sequential() {
+        assert_eq!(peak(false).await, 3);
+        assert_eq!(peak(true).await, 1);
+    }
+
+    /// 只处理群消息
+    struct GroupOnly;
+
+    #[async_trait]
+    impl Application for GroupOnly {
+        fn name(&self) -> &str {
+            "group_only"
+        }
+
+        async fn on_group_message(&self, _event: &GroupMessage) -> Result<Propagation> {
+            Ok(Propagation::Stop)
+        }
+    }
+
+    #[tokio::test]
+    async fn default_on_event_routes_by_type() {
+        let app = GroupOnly;
+        let group = app.on_event(event(group_message(1, 2, "hi"))).await;
+        let private = app.on_event(event(private_message(2, "hi"))).await;
+        assert_eq!(group.unwrap(), Propagation::Stop);
+        assert_eq!(private.unwrap(), Propagation::Continue);
+    }
 }
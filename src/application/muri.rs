@@ -1,12 +1,16 @@
-use std::sync::Arc;
-
 use anyhow::Result;
 use async_trait::async_trait;
 use dashmap::DashMap;
 use lazy_static::lazy_static;
 use rand::seq::IndexedRandom;
 
-use crate::protocol::event::Event;
+use crate::{
+    application::{
+        Priority, Propagation,
+        command::{self, Command},
+    },
+    protocol::event::{EventKind, MessageEvent},
+};
 
 lazy_static! {
     static ref DB: DashMap<String, Vec<String>> = {
@@ -39,28 +43,47 @@ lazy_static! {
     };
 }
 
-pub struct MuriApp;
+pub struct MuriApp {
+    commands: Vec<Command>,
+}
 
 #[async_trait]
 impl super::Application for MuriApp {
     fn name(&self) -> &str {
-        "template"
+        "muri"
+    }
+
+    fn subscriptions(&self) -> &[EventKind] {
+        &[EventKind::Message]
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Blocking(30)
+    }
+
+    fn commands(&self) -> &[Command] {
+        &self.commands
     }
 
-    async fn on_event(&mut self, event: Arc<Event>) -> Result<()> {
-        if let Event::MessageEvent(event) = event.as_ref() {
-            let raw = event.raw_message();
-            if let Some(key) = DB.get(raw) {
-                let choise = key.choose(&mut rand::rng()).unwrap();
-                event.reply(choise.to_string(), false).await?;
-            }
+    async fn on_message(&self, event: &MessageEvent) -> Result<Propagation> {
+        if let Some((command, _)) = command::route(&self.commands, event).await?
+            && let Some(key) = DB.get(&command.name)
+        {
+            let choise = key.choose(&mut rand::rng()).unwrap();
+            event.reply(choise.to_string(), false).await?;
+            return Ok(Propagation::Stop);
         }
-        Ok(())
+        Ok(Propagation::Continue)
     }
 }
 
 impl MuriApp {
     pub fn new() -> Self {
-        Self {}
+        let mut commands: Vec<Command> = DB
+            .iter()
+            .map(|entry| Command::new(entry.key(), "随机抽一个").prefix(""))
+            .collect();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { commands }
     }
 }
@@ -0,0 +1,125 @@
+// 用户权限，由主人列表、配置的超级用户、主人授予的角色与群角色共同决定
+//
+// 授予的角色保存在 `permission_file`（默认 `permissions.json`）中
+use crate::{
+    config,
+    protocol::event::{GroupRole, MessageEvent},
+};
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, sync::RwLock};
+
+/// 权限等级，从低到高排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Everyone,
+    /// 群主或群管理员，被授予时在所有群生效
+    GroupAdmin,
+    Superuser,
+    Owner,
+}
+
+impl Permission {
+    /// 消息发送者拥有的权限
+    pub fn of(event: &MessageEvent) -> Self {
+        let role = match event {
+            MessageEvent::Group(e) => e.sender.role.as_ref(),
+            MessageEvent::Private(_) => None,
+        };
+        level(event.user_id(), role)
+    }
+
+    /// 可由主人授予的角色名
+    pub fn parse_role(role: &str) -> Option<Self> {
+        match role {
+            "admin" => Some(Permission::GroupAdmin),
+            "superuser" => Some(Permission::Superuser),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Permission::Everyone => write!(f, "所有人"),
+            Permission::GroupAdmin => write!(f, "群管理"),
+            Permission::Superuser => write!(f, "超级用户"),
+            Permission::Owner => write!(f, "主人"),
+        }
+    }
+}
+
+lazy_static! {
+    static ref ROLES: RwLock<HashMap<i64, Permission>> = RwLock::new(load());
+}
+
+/// 按当前配置重新读取 `permission_file`
+pub fn reload() {
+    *ROLES.write().unwrap() = load();
+}
+
+fn load() -> HashMap<i64, Permission> {
+    let path = config::get().permission_file.clone();
+    match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            log::warn!("invalid {}: {}", path, e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save(roles: &HashMap<i64, Permission>) -> Result<()> {
+    let path = &config::get().permission_file;
+    std::fs::write(path, serde_json::to_string_pretty(roles)?)?;
+    Ok(())
+}
+
+pub fn is_owner(user_id: i64) -> bool {
+    config::get().owner.contains(&user_id)
+}
+
+/// 用户的权限，`role` 为其在当前群的角色
+pub fn level(user_id: i64, role: Option<&GroupRole>) -> Permission {
+    let config = config::get();
+    if is_owner(user_id) {
+        return Permission::Owner;
+    }
+    if config.superusers.contains(&user_id) {
+        return Permission::Superuser;
+    }
+    let granted = ROLES
+        .read()
+        .unwrap()
+        .get(&user_id)
+        .copied()
+        .unwrap_or(Permission::Everyone);
+    let group = match role {
+        Some(GroupRole::Owner | GroupRole::Admin) => Permission::GroupAdmin,
+        _ => Permission::Everyone,
+    };
+    granted.max(group)
+}
+
+/// 授予角色并保存，不能授予主人
+pub fn grant(user_id: i64, permission: Permission) -> Result<()> {
+    if permission == Permission::Owner {
+        anyhow::bail!("owner can only be set in config");
+    }
+    let mut roles = ROLES.write().unwrap();
+    roles.insert(user_id, permission);
+    save(&roles)
+}
+
+/// 撤销授予的角色并保存，返回是否存在
+pub fn revoke(user_id: i64) -> Result<bool> {
+    let mut roles = ROLES.write().unwrap();
+    let existed = roles.remove(&user_id).is_some();
+    if existed {
+        save(&roles)?;
+    }
+    Ok(existed)
+}
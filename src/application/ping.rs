@@ -1,13 +1,22 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use std::{sync::Arc, time::SystemTime};
+use std::time::SystemTime;
 
 use crate::{
-    config,
-    protocol::{adapter::ROUND_START_TIME, event::Event},
+    application::{
+        Priority, Propagation,
+        command::{self, Command},
+        permission::Permission,
+    },
+    protocol::{
+        adapter::ROUND_START_TIME,
+        event::{self, EventKind, MessageEvent},
+    },
 };
 
-pub struct PingApp;
+pub struct PingApp {
+    commands: Vec<Command>,
+}
 
 #[async_trait]
 impl super::Application for PingApp {
@@ -15,27 +24,63 @@ impl super::Application for PingApp {
         "ping"
     }
 
-    async fn on_event(&mut self, event: Arc<Event>) -> Result<()> {
-        match event.as_ref() {
-            Event::MessageEvent(event) => {
-                if event.user_id() == config::OWNER && event.raw_message() == "ping" {
-                    event.reply("pong", true).await?;
-                }
-                if event.raw_message() == "!perf" {
+    fn subscriptions(&self) -> &[EventKind] {
+        &[EventKind::Message]
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Blocking(10)
+    }
+
+    fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    async fn on_message(&self, event: &MessageEvent) -> Result<Propagation> {
+        if let Some((command, _)) = command::route(&self.commands, event).await? {
+            match command.name.as_str() {
+                "ping" => _ = event.reply("pong", true).await?,
+                "perf" => {
                     let cur_time = SystemTime::now();
                     let round_time = *ROUND_START_TIME.lock().await;
                     let dur = cur_time.duration_since(round_time)?;
-                    event.reply(format!("tpr: {:?}", dur), true).await?;
+                    let reply = format!("tpr: {:?}\nunknown: {}", dur, event::unknown_count());
+                    event.reply(reply, true).await?;
                 }
+                _ => {}
             }
-            _ => {}
+            return Ok(Propagation::Stop);
         }
-        Ok(())
+        Ok(Propagation::Continue)
     }
 }
 
 impl PingApp {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            commands: vec![
+                Command::new("ping", "检查 bot 是否在线")
+                    .prefix("")
+                    .permission(Permission::Owner),
+                Command::new("perf", "本轮事件的处理耗时"),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::mock::MockServer;
+
+    #[tokio::test]
+    async fn owner_ping_replies_pong() {
+        let server = MockServer::start(10002, "token = \"\"\nowner = 114514\n").await;
+        server.replay(include_str!("../../tests/fixtures/ping.jsonl"));
+
+        let sent = server.wait_action("send_private_msg").await;
+        assert_eq!(sent.params["user_id"], 114514);
+        let segments = sent.params["message"].as_array().unwrap();
+        assert_eq!(segments[0]["type"], "reply");
+        assert_eq!(segments[1]["data"]["text"], "pong");
     }
 }
@@ -0,0 +1,162 @@
+// 会话，应用可以挂起等待同一用户的下一条消息，如“回复序号选择”
+//
+// 分发器在中间件之后、应用之前检查等待中的会话，被会话接收的消息不再分发给应用
+use crate::{
+    application::switchboard::Scope,
+    protocol::event::{Event, MessageEvent},
+};
+use lazy_static::lazy_static;
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+struct Waiter {
+    self_id: i64,
+    user_id: i64,
+    /// 为 `None` 时接受来自任意群或私聊的消息
+    scope: Option<Scope>,
+    sender: oneshot::Sender<Arc<Event>>,
+}
+
+impl Waiter {
+    fn matches(&self, event: &MessageEvent) -> bool {
+        self.self_id == event.self_id()
+            && self.user_id == event.user_id()
+            && self.scope.is_none_or(|scope| scope == Scope::of(event))
+    }
+}
+
+lazy_static! {
+    static ref WAITERS: Mutex<Vec<Waiter>> = Mutex::new(Vec::new());
+}
+
+/// 会话等到的消息
+pub struct NextMessage(Arc<Event>);
+
+impl Deref for NextMessage {
+    type Target = MessageEvent;
+
+    fn deref(&self) -> &Self::Target {
+        match self.0.as_ref() {
+            Event::MessageEvent(e) => e,
+            _ => unreachable!("sessions only receive message events"),
+        }
+    }
+}
+
+/// 等待同一用户在同一群（私聊）中的下一条消息，超时返回 `None`
+///
+/// 调用时即开始等待，可以先取得 future 再发送提示，以免错过很快的回复
+pub fn wait_for_next_message(
+    event: &MessageEvent,
+    timeout: Duration,
+) -> impl Future<Output = Option<NextMessage>> + use<> {
+    wait_for(
+        event.self_id(),
+        event.user_id(),
+        Some(Scope::of(event)),
+        timeout,
+    )
+}
+
+/// 等待用户的下一条消息，`scope` 为 `None` 时不限群或私聊
+pub fn wait_for(
+    self_id: i64,
+    user_id: i64,
+    scope: Option<Scope>,
+    timeout: Duration,
+) -> impl Future<Output = Option<NextMessage>> {
+    let (sender, receiver) = oneshot::channel();
+    {
+        let mut waiters = WAITERS.lock().unwrap();
+        waiters.retain(|w| !w.sender.is_closed());
+        waiters.push(Waiter {
+            self_id,
+            user_id,
+            scope,
+            sender,
+        });
+    }
+    async move {
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(event)) => Some(NextMessage(event)),
+            _ => None,
+        }
+    }
+}
+
+/// 将消息交给最早开始等待的匹配会话，返回是否被接收
+pub fn resume(event: &Arc<Event>) -> bool {
+    let Event::MessageEvent(message) = event.as_ref() else {
+        return false;
+    };
+    let mut waiters = WAITERS.lock().unwrap();
+    waiters.retain(|w| !w.sender.is_closed());
+    let Some(index) = waiters.iter().position(|w| w.matches(message)) else {
+        return false;
+    };
+    waiters.remove(index).sender.send(event.clone()).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resume, wait_for, wait_for_next_message};
+    use crate::{
+        application::switchboard::Scope,
+        protocol::{
+            event::{Event, MessageEvent},
+            mock::{group_message, private_message},
+        },
+    };
+    use serde_json::Value;
+    use std::{sync::Arc, time::Duration};
+
+    const SELF_ID: i64 = 20001;
+
+    fn event(mut raw: Value) -> Arc<Event> {
+        raw["time"] = 0.into();
+        raw["self_id"] = SELF_ID.into();
+        raw["message_id"] = 0.into();
+        Arc::new(serde_json::from_value(raw).unwrap())
+    }
+
+    fn message(event: &Event) -> &MessageEvent {
+        match event {
+            Event::MessageEvent(e) => e,
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_same_user_in_same_group() {
+        let question = event(group_message(1, 2, "选哪个？"));
+        let waiting = tokio::spawn(async move {
+            wait_for_next_message(message(&question), Duration::from_secs(1))
+                .await
+                .map(|e| e.raw_message().to_string())
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert!(!resume(&event(group_message(1, 3, "别人"))));
+        assert!(!resume(&event(group_message(9, 2, "别的群"))));
+        assert!(!resume(&event(private_message(2, "私聊"))));
+        assert!(resume(&event(group_message(1, 2, "2"))));
+        assert_eq!(waiting.await.unwrap().as_deref(), Some("2"));
+        assert!(!resume(&event(group_message(1, 2, "3"))));
+    }
+
+    #[tokio::test]
+    async fn any_scope_and_timeout() {
+        let waiting = tokio::spawn(wait_for(SELF_ID, 5, None, Duration::from_secs(1)));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(resume(&event(private_message(5, "ok"))));
+        assert!(waiting.await.unwrap().is_some());
+
+        let expired = wait_for(SELF_ID, 6, Some(Scope::User(6)), Duration::from_millis(10));
+        assert!(expired.await.is_none());
+        assert!(!resume(&event(private_message(6, "late"))));
+    }
+}
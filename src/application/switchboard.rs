@@ -0,0 +1,183 @@
+// 应用开关，按群或私聊用户启用、停用应用，由分发事件时统一检查
+//
+// 未设置过的群先看 `[apps.<name>] enabled_groups`，再按应用的 `default_enabled` 处理，
+// 私聊默认启用；修改保存在 `switch_file` 中，重新加载配置时重新读取
+use crate::{config, protocol::event::MessageEvent};
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::RwLock,
+};
+
+/// 开关的作用范围
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Group(i64),
+    User(i64),
+}
+
+impl Scope {
+    pub fn of(event: &MessageEvent) -> Self {
+        match event {
+            MessageEvent::Group(e) => Scope::Group(e.group_id),
+            MessageEvent::Private(e) => Scope::User(e.user_id),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Group(id) => write!(f, "群 {}", id),
+            Scope::User(id) => write!(f, "用户 {}", id),
+        }
+    }
+}
+
+/// 单个应用的开关
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Switches {
+    #[serde(default)]
+    groups: HashMap<i64, bool>,
+    #[serde(default)]
+    users: HashMap<i64, bool>,
+}
+
+/// 配置中默认启用的群
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Seed {
+    enabled_groups: Vec<i64>,
+    /// 旧版 chat、cat 的白名单，等同于 `enabled_groups`
+    white_groups: Vec<i64>,
+}
+
+lazy_static! {
+    static ref SWITCHES: RwLock<HashMap<String, Switches>> = RwLock::new(load());
+    static ref SEEDS: RwLock<HashMap<String, HashSet<i64>>> = RwLock::new(load_seeds());
+}
+
+/// 按当前配置重新读取 `switch_file` 与各应用的 `enabled_groups`
+pub fn reload() {
+    *SWITCHES.write().unwrap() = load();
+    *SEEDS.write().unwrap() = load_seeds();
+}
+
+fn load_seeds() -> HashMap<String, HashSet<i64>> {
+    let config = config::get();
+    let mut seeds = HashMap::new();
+    for app in config.app_names() {
+        let seed: Seed = match config.app(app) {
+            Ok(seed) => seed,
+            Err(e) => {
+                log::warn!("{}", e);
+                continue;
+            }
+        };
+        if !seed.white_groups.is_empty() {
+            log::warn!(
+                "[apps.{}] white_groups is deprecated, rename it to enabled_groups",
+                app
+            );
+        }
+        let groups: HashSet<i64> = seed
+            .enabled_groups
+            .into_iter()
+            .chain(seed.white_groups)
+            .collect();
+        if !groups.is_empty() {
+            seeds.insert(app.to_string(), groups);
+        }
+    }
+    seeds
+}
+
+fn load() -> HashMap<String, Switches> {
+    let path = config::get().switch_file.clone();
+    match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            log::warn!("invalid {}: {}", path, e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// 应用在 `scope` 内是否启用，`default` 为应用在群内的默认状态
+pub fn enabled(app: &str, scope: Scope, default: bool) -> bool {
+    let switches = SWITCHES.read().unwrap();
+    let switches = switches.get(app);
+    match scope {
+        Scope::Group(id) => switches
+            .and_then(|s| s.groups.get(&id))
+            .copied()
+            .unwrap_or_else(|| {
+                let seeded = SEEDS
+                    .read()
+                    .unwrap()
+                    .get(app)
+                    .is_some_and(|g| g.contains(&id));
+                seeded || default
+            }),
+        Scope::User(id) => switches
+            .and_then(|s| s.users.get(&id))
+            .copied()
+            .unwrap_or(true),
+    }
+}
+
+/// 设置开关并保存
+pub fn set(app: &str, scope: Scope, enabled: bool) -> Result<()> {
+    let mut switches = SWITCHES.write().unwrap();
+    let entry = switches.entry(app.to_string()).or_default();
+    match scope {
+        Scope::Group(id) => entry.groups.insert(id, enabled),
+        Scope::User(id) => entry.users.insert(id, enabled),
+    };
+    std::fs::write(
+        &config::get().switch_file,
+        serde_json::to_string_pretty(&*switches)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scope, enabled, set};
+    use crate::protocol::mock::MockServer;
+
+    #[tokio::test]
+    async fn reload_reads_new_file_and_config_groups() {
+        let dir = std::env::temp_dir();
+        let old = dir.join("kanami-test-switches-old.json");
+        let new = dir.join("kanami-test-switches-new.json");
+        std::fs::write(&new, r#"{"cat": {"groups": {"7": false}}}"#).unwrap();
+        let config = |file: &std::path::Path| {
+            format!(
+                "token = \"\"\nowner = 1\nswitch_file = {:?}\n\
+                 [apps.cat]\nwhite_groups = [5, 7]\n[apps.gscore]\nenabled_groups = [6]\n",
+                file.display().to_string()
+            )
+        };
+
+        let server = MockServer::start(10011, &config(&old)).await;
+        set("cat", Scope::Group(8), true).unwrap();
+        assert!(enabled("cat", Scope::Group(8), false));
+        assert!(enabled("cat", Scope::Group(7), false));
+        drop(server);
+
+        let _server = MockServer::start(10011, &config(&new)).await;
+        assert!(!enabled("cat", Scope::Group(8), false));
+        assert!(enabled("cat", Scope::Group(5), false));
+        assert!(!enabled("cat", Scope::Group(7), false));
+        assert!(enabled("gscore", Scope::Group(6), false));
+        assert!(!enabled("chat", Scope::Group(6), false));
+        let saved = std::fs::read_to_string(&new).unwrap();
+        assert!(!saved.contains("\"8\""));
+        _ = std::fs::remove_file(&old);
+        _ = std::fs::remove_file(&new);
+    }
+}
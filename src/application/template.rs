@@ -1,11 +1,15 @@
-use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 
-use crate::protocol::event::Event;
+use crate::application::command::{self, Arg, Command};
+use crate::application::{Priority, Propagation, session};
+use crate::protocol::event::MessageEvent;
 
-struct TemplateApp;
+struct TemplateApp {
+    commands: Vec<Command>,
+}
 
 #[async_trait]
 impl super::Application for TemplateApp {
@@ -13,13 +17,75 @@ impl super::Application for TemplateApp {
         "template"
     }
 
-    async fn on_event(&mut self, event: Arc<Event>) -> Result<()> {
-        todo!()
+    fn priority(&self) -> Priority {
+        Priority::Blocking(50)
+    }
+
+    fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    async fn on_message(&self, event: &MessageEvent) -> Result<Propagation> {
+        if let Some((command, args)) = command::route(&self.commands, event).await? {
+            match command.name.as_str() {
+                "echo" => match args.text("text") {
+                    Some(text) => _ = event.reply(text, false).await?,
+                    None => _ = event.reply("要复读什么？", true).await?,
+                },
+                "choose" => {
+                    // 先开始等待再提问，然后等同一用户在同一群（私聊）的回复
+                    let next = session::wait_for_next_message(event, Duration::from_secs(30));
+                    event.reply("1. 猫 2. 狗，回复序号选择", true).await?;
+                    let reply = match next.await {
+                        Some(reply) => reply,
+                        None => return Ok(Propagation::Stop),
+                    };
+                    let choice = match reply.raw_message().trim() {
+                        "1" => "猫",
+                        "2" => "狗",
+                        _ => "无效的序号",
+                    };
+                    reply.reply(choice, true).await?;
+                }
+                _ => return Ok(Propagation::Continue),
+            }
+            return Ok(Propagation::Stop);
+        }
+        Ok(Propagation::Continue)
     }
 }
 
 impl TemplateApp {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            commands: vec![
+                Command::new("echo", "复读").arg(Arg::rest("text")),
+                Command::new("choose", "回复序号做选择"),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemplateApp;
+    use crate::{
+        application::{Application, Propagation},
+        protocol::mock::{MockServer, group_message},
+    };
+
+    #[tokio::test]
+    async fn echo_replies_text() {
+        let server = MockServer::start(10021, "token = \"\"\nowner = 1\n").await;
+        let app = TemplateApp::new();
+        let mut raw = group_message(303, 2, "!echo 你好");
+        raw["time"] = 0.into();
+        raw["self_id"] = 10021.into();
+        raw["message_id"] = 0.into();
+        let event = serde_json::from_value(raw).unwrap();
+
+        assert_eq!(app.on_message(&event).await.unwrap(), Propagation::Stop);
+        let sent = server.wait_action("send_group_msg").await;
+        assert_eq!(sent.params["message"][0]["data"]["text"], "你好");
     }
 }
@@ -1,10 +0,0 @@
-pub const ENDPOINT: &str = "ws://127.0.0.1:3001";
-pub const TOKEN: &str = "token";
-pub const OWNER: i64 = 1145141919810;
-pub const MAIN_GROOUP: i64 = 1145141919810;
-
-pub const GSCORE_ENDPOINT: &str = "ws://127.0.0.1:8765/ws/kanami";
-pub const GSCORE_BOTID: &str = "Kanami";
-pub const GSCORE_ENABLED_GROUP: i64 = 1145141919810;
-pub const GSCORE_NODE_SENDER_ID: &str = "1145141919810";
-pub const GSCORE_NODE_SENDER_NICKNAME: &str = "Kanami";
\ No newline at end of file
@@ -0,0 +1,370 @@
+// 运行时配置，启动时从 TOML 文件加载，收到 SIGHUP 时重新加载
+//
+// 路径优先级：命令行第一个参数 > `KANAMI_CONFIG` > `config.toml`
+// 环境变量 `KANAMI__<KEY>__<SUBKEY>` 覆盖对应配置项，如 `KANAMI__APPS__CHAT__OPENAI_TOKEN`
+
+use anyhow::{Result, anyhow};
+use chrono::NaiveTime;
+use serde::{
+    Deserialize, Deserializer,
+    de::{DeserializeOwned, Error},
+};
+use std::{
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use toml::{Table, Value};
+
+const DEFAULT_PATH: &str = "config.toml";
+const ENV_PREFIX: &str = "KANAMI__";
+
+/// 与 OneBot 实现的连接方式
+#[derive(Debug, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// 正向 WebSocket，主动连接 `endpoint`
+    #[default]
+    Forward,
+    /// 反向 WebSocket，监听 `listen` 等待连接
+    Reverse,
+    /// HTTP API 调用 `http_endpoint`，监听 `listen` 接收 HTTP POST 上报
+    #[cfg(feature = "http")]
+    Http,
+    /// 回放 `replay` 指定的录制文件，结束后退出
+    Replay,
+}
+
+/// 正向模式下额外连接的账号
+#[derive(Debug, Deserialize)]
+pub struct Account {
+    pub endpoint: String,
+    /// 缺省时使用主账号的 `token`
+    pub token: Option<String>,
+}
+
+/// 分发给应用前的全局过滤，见 `application::middleware`
+#[derive(Debug, Deserialize)]
+pub struct Filter {
+    /// 忽略这些用户的消息
+    #[serde(default)]
+    pub blacklist_users: Vec<i64>,
+    /// 忽略这些群的消息
+    #[serde(default)]
+    pub blacklist_groups: Vec<i64>,
+    /// 忽略账号自己发出的消息
+    #[serde(default = "default_true", deserialize_with = "lenient")]
+    pub ignore_self: bool,
+    /// 静默时段，期间只处理主人的消息
+    #[serde(default)]
+    pub mute: Vec<MuteWindow>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            blacklist_users: Vec::new(),
+            blacklist_groups: Vec::new(),
+            ignore_self: true,
+            mute: Vec::new(),
+        }
+    }
+}
+
+/// 每日的静默时段，写作 `"23:00-07:00"`，可以跨越午夜
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct MuteWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl MuteWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for MuteWindow {
+    type Error = String;
+
+    fn try_from(text: String) -> std::result::Result<Self, Self::Error> {
+        let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").ok();
+        text.split_once('-')
+            .and_then(|(start, end)| {
+                Some(Self {
+                    start: parse(start)?,
+                    end: parse(end)?,
+                })
+            })
+            .ok_or_else(|| format!("invalid mute window {:?}, expected HH:MM-HH:MM", text))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub mode: Mode,
+    /// OneBot WebSocket 地址（正向）
+    #[serde(default)]
+    pub endpoint: String,
+    /// 监听地址（反向 / HTTP 上报）
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// OneBot HTTP API 地址
+    #[cfg(feature = "http")]
+    #[serde(default)]
+    pub http_endpoint: String,
+    /// HTTP 上报签名密钥，为空时不校验 `X-Signature`
+    #[cfg(feature = "http")]
+    #[serde(default)]
+    pub secret: String,
+    /// OneBot access token
+    pub token: String,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    /// 收发帧的录制文件（JSONL），为空时不录制
+    #[serde(default)]
+    pub record: String,
+    /// 回放模式读取的录制文件
+    #[serde(default)]
+    pub replay: String,
+    /// 机器人主人 QQ 号，可以是一个或多个
+    #[serde(deserialize_with = "one_or_many")]
+    pub owner: Vec<i64>,
+    /// 超级用户，权限仅次于主人
+    #[serde(default)]
+    pub superusers: Vec<i64>,
+    /// 主人授予的角色保存位置
+    #[serde(default = "default_permission_file")]
+    pub permission_file: String,
+    /// 应用开关保存位置
+    #[serde(default = "default_switch_file")]
+    pub switch_file: String,
+    #[serde(default)]
+    pub filter: Filter,
+    /// 各应用的配置段 `[apps.<name>]`
+    #[serde(default)]
+    apps: Table,
+}
+
+fn default_listen() -> String {
+    "0.0.0.0:3002".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_permission_file() -> String {
+    "permissions.json".to_string()
+}
+
+fn default_switch_file() -> String {
+    "switches.json".to_string()
+}
+
+/// 单个或多个账号，也接受环境变量给出的 `1,2` 形式
+fn one_or_many<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<Vec<i64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(i64),
+        Many(Vec<i64>),
+        Text(String),
+    }
+    Ok(match OneOrMany::deserialize(d)? {
+        OneOrMany::One(id) => vec![id],
+        OneOrMany::Many(ids) => ids,
+        OneOrMany::Text(text) => text
+            .split(',')
+            .map(|id| id.trim().parse().map_err(D::Error::custom))
+            .collect::<std::result::Result<_, _>>()?,
+    })
+}
+
+/// 数字或布尔配置项，也接受字符串形式
+///
+/// 环境变量覆盖文件中没有的配置项时总是字符串，由此按字段类型解析
+pub fn lenient<'de, D, T>(d: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ValueOrText<T> {
+        Value(T),
+        Text(String),
+    }
+    match ValueOrText::<T>::deserialize(d)? {
+        ValueOrText::Value(value) => Ok(value),
+        ValueOrText::Text(text) => text.trim().parse().map_err(D::Error::custom),
+    }
+}
+
+static CONFIG: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+
+impl Config {
+    /// 配置了 `[apps.<name>]` 的应用名
+    pub fn app_names(&self) -> impl Iterator<Item = &str> {
+        self.apps.keys().map(String::as_str)
+    }
+
+    /// 将 `[apps.<name>]` 反序列化为应用自己的配置，缺省时按空表处理
+    pub fn app<T: DeserializeOwned>(&self, name: &str) -> Result<T> {
+        let section = self
+            .apps
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| Value::Table(Table::new()));
+        section
+            .try_into()
+            .map_err(|e| anyhow!("invalid config [apps.{}]: {}", name, e))
+    }
+}
+
+pub fn path() -> PathBuf {
+    std::env::args()
+        .nth(1)
+        .or_else(|| std::env::var("KANAMI_CONFIG").ok())
+        .unwrap_or_else(|| DEFAULT_PATH.to_string())
+        .into()
+}
+
+/// 读取配置文件并替换当前配置，解析失败时保留旧配置
+///
+/// `endpoint`、`token` 在下次重连时生效
+pub fn load() -> Result<()> {
+    let path = path();
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("failed to read {}: {}", path.display(), e))?;
+    let config = parse(&text)?;
+    log::info!("config loaded from {}", path.display());
+    *CONFIG.write().unwrap() = Some(Arc::new(config));
+    Ok(())
+}
+
+/// 直接从文本加载配置，测试时使用
+///
+/// 未指定 `permission_file`、`switch_file` 时使用新的临时文件，不读写工作目录中的真实文件
+#[cfg(test)]
+pub fn load_str(text: &str) -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static LOADS: AtomicUsize = AtomicUsize::new(0);
+    let mut config = parse(text)?;
+    let n = LOADS.fetch_add(1, Ordering::Relaxed);
+    let temp = |name: &str| {
+        let file = format!("kanami-test-{}-{}-{}", std::process::id(), n, name);
+        std::env::temp_dir().join(file).display().to_string()
+    };
+    let table: Table = toml::from_str(text)?;
+    if !table.contains_key("permission_file") {
+        config.permission_file = temp("permissions.json");
+    }
+    if !table.contains_key("switch_file") {
+        config.switch_file = temp("switches.json");
+    }
+    *CONFIG.write().unwrap() = Some(Arc::new(config));
+    Ok(())
+}
+
+fn parse(text: &str) -> Result<Config> {
+    parse_with_env(text, std::env::vars())
+}
+
+fn parse_with_env(text: &str, vars: impl Iterator<Item = (String, String)>) -> Result<Config> {
+    let mut table: Table = toml::from_str(text)?;
+    apply_env(&mut table, vars);
+    Ok(Value::Table(table).try_into()?)
+}
+
+pub fn get() -> Arc<Config> {
+    CONFIG.read().unwrap().clone().expect("config not loaded")
+}
+
+fn apply_env(table: &mut Table, vars: impl Iterator<Item = (String, String)>) {
+    for (key, raw) in vars {
+        let Some(path) = key.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let keys: Vec<String> = path.split("__").map(|k| k.to_lowercase()).collect();
+        if !set(table, &keys, raw) {
+            log::warn!("config: ignored env override {}", key);
+        }
+    }
+}
+
+fn set(table: &mut Table, keys: &[String], raw: String) -> bool {
+    let Some((last, parents)) = keys.split_last() else {
+        return false;
+    };
+    let mut cur = table;
+    for key in parents {
+        cur = match cur
+            .entry(key.as_str())
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            Value::Table(t) => t,
+            _ => return false,
+        };
+    }
+    let value = parse_env(cur.get(last), raw);
+    cur.insert(last.clone(), value);
+    true
+}
+
+/// 按原配置项的类型解析环境变量；原先不存在时保留字符串，由字段的反序列化决定类型，
+/// 以免数字形式的 token 等被当成整数
+fn parse_env(old: Option<&Value>, raw: String) -> Value {
+    match old {
+        Some(Value::Integer(_)) => raw
+            .parse()
+            .map(Value::Integer)
+            .unwrap_or(Value::String(raw)),
+        Some(Value::Float(_)) => raw.parse().map(Value::Float).unwrap_or(Value::String(raw)),
+        Some(Value::Boolean(_)) => raw
+            .parse()
+            .map(Value::Boolean)
+            .unwrap_or(Value::String(raw)),
+        _ => Value::String(raw),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_with_env;
+    use serde::Deserialize;
+
+    #[test]
+    fn env_overrides_follow_field_types() {
+        #[derive(Deserialize)]
+        struct Chat {
+            #[serde(deserialize_with = "super::lenient")]
+            rate_limit_max: usize,
+        }
+
+        let vars = [
+            ("KANAMI__TOKEN", "123456"),
+            ("KANAMI__OWNER", "1, 2"),
+            ("KANAMI__FILTER__IGNORE_SELF", "false"),
+            ("KANAMI__APPS__CHAT__RATE_LIMIT_MAX", "5"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let config = parse_with_env("token = \"\"\nowner = 1\n", vars.clone().into_iter()).unwrap();
+        assert_eq!(config.token, "123456");
+        assert_eq!(config.owner, [1, 2]);
+        assert!(!config.filter.ignore_self);
+        assert_eq!(config.app::<Chat>("chat").unwrap().rate_limit_max, 5);
+
+        let config = parse_with_env("owner = 3\n", vars.into_iter()).unwrap();
+        assert_eq!(config.token, "123456");
+    }
+}
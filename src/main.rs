@@ -1,5 +1,6 @@
 use crate::protocol::adapter;
 use anyhow::Result;
+use std::time::Duration;
 // use tikv_jemallocator::Jemalloc;
 
 mod application;
@@ -14,5 +15,61 @@ mod protocol;
 async fn main() -> Result<()> {
     logger::init();
     log::info!("Hello Kanami Bot!");
-    adapter::launch().await
+    config::load()?;
+    application::startup().await;
+    #[cfg(unix)]
+    tokio::spawn(reload_on_hangup());
+    tokio::select! {
+        _ = adapter::launch() => {}
+        _ = shutdown_signal() => {}
+    }
+    log::info!("shutting down");
+    if tokio::time::timeout(Duration::from_secs(5), application::shutdown())
+        .await
+        .is_err()
+    {
+        log::warn!("shutdown timed out");
+    }
+    Ok(())
+}
+
+/// 等待 SIGINT 或 SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            },
+            Err(e) => {
+                log::warn!("failed to listen SIGTERM: {}", e);
+                _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(unix)]
+async fn reload_on_hangup() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            log::warn!("failed to listen SIGHUP: {}", e);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        log::info!("SIGHUP received, reloading config");
+        application::reload_config().await;
+        adapter::reconcile_accounts();
+    }
 }
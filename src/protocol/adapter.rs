@@ -1,25 +1,55 @@
-// to connect to a ws backend
-use crate::{application::APPS, config, protocol::event::Event};
+// to connect to a ws backend, or accept connections from it in reverse mode
+// the event loop itself only talks to a `Transport`
+use crate::{
+    application::{
+        self, AppEntry, DISPATCH_ORDER, Priority, Propagation, middleware, session,
+        switchboard::{self, Scope},
+    },
+    config::{self, Mode},
+    protocol::{
+        error::ActionError,
+        event::{self, Event},
+        record, replay,
+        transport::{Transport, WsStream, WsTransport},
+    },
+};
 use anyhow::{Result, anyhow};
 use dashmap::DashMap;
-use futures_util::{SinkExt, StreamExt, stream::SplitStream};
-use serde::{Deserialize, Serialize};
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::Value;
 use std::{
+    collections::HashMap,
     sync::Arc,
     time::{Duration, SystemTime},
 };
-use tokio::sync::{Mutex, mpsc, oneshot};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-
-type WsStream =
-    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{Mutex, mpsc, oneshot, watch},
+};
+use tokio_tungstenite::{
+    MaybeTlsStream, accept_hdr_async, connect_async,
+    tungstenite::{
+        Message,
+        handshake::server::{
+            ErrorResponse, Request as HandshakeRequest, Response as HandshakeResponse,
+        },
+        http::StatusCode,
+    },
+};
 
 pub static ROUND_START_TIME: Mutex<SystemTime> = Mutex::const_new(SystemTime::UNIX_EPOCH);
 
+lazy_static! {
+    /// 正向模式下 `accounts` 中各账号的连接任务，按 `endpoint` 区分，发送 `true` 使其停止
+    static ref ACCOUNTS: std::sync::Mutex<HashMap<String, watch::Sender<bool>>> =
+        Default::default();
+}
+
 #[derive(Debug)]
 pub(crate) struct PendingRequest {
-    sender: oneshot::Sender<Response>,
+    sender: oneshot::Sender<ResponseResult>,
     created_at: std::time::Instant,
 }
 
@@ -33,6 +63,32 @@ pub struct Response {
     echo: Option<String>,
 }
 
+pub type ResponseResult = Result<Response, ActionError>;
+
+impl Response {
+    /// 检查响应状态，`ok` 与 `async` 视为成功
+    pub fn check(self) -> Result<Self, ActionError> {
+        match self.status.as_str() {
+            "ok" | "async" => Ok(self),
+            _ => Err(ActionError::Failed {
+                retcode: self.retcode,
+                message: self.message,
+            }),
+        }
+    }
+
+    /// 检查响应状态并将 `data` 反序列化为 `T`
+    ///
+    /// `async` 表示实现已受理、稍后执行，此时没有数据，返回 `None`
+    pub fn into_data<T: DeserializeOwned>(self) -> Result<Option<T>, ActionError> {
+        let res = self.check()?;
+        match res.data {
+            None | Some(Value::Null) if res.status == "async" => Ok(None),
+            data => Ok(Some(serde_json::from_value(data.unwrap_or_default())?)),
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct Request {
     pub action: String,
@@ -41,65 +97,102 @@ pub struct Request {
     #[serde(skip_serializing)]
     pub created_at: std::time::Instant,
     #[serde(skip_serializing)]
-    pub sender: oneshot::Sender<Response>,
+    pub sender: oneshot::Sender<ResponseResult>,
+}
+
+/// 解析事件，经过中间件链后分发给订阅了该类事件的应用
+///
+/// 等待中的会话优先接收消息；阻塞应用按优先级依次处理，直到某个应用停止传播，
+/// 其余应用随后并发处理。消息事件只分发给在该群或私聊中启用的应用
+pub(crate) fn dispatch(raw: Value) {
+    match serde_json::from_value::<Event>(raw) {
+        Ok(event) => {
+            event::report_unknown(&event);
+            if !middleware::accept(&event) {
+                return;
+            }
+            let kind = event.kind();
+            let scope = match &event {
+                Event::MessageEvent(e) => Some(Scope::of(e)),
+                _ => None,
+            };
+            let event = Arc::new(event);
+            if session::resume(&event) {
+                return;
+            }
+            tokio::spawn(async move {
+                for app in DISPATCH_ORDER.iter().filter(|app| app.subscribes(kind)) {
+                    let event = event.clone();
+                    match app.priority() {
+                        Priority::Blocking(_) => {
+                            if deliver(app, event, scope).await == Propagation::Stop {
+                                return;
+                            }
+                        }
+                        Priority::Concurrent => {
+                            tokio::spawn(deliver(app, event, scope));
+                        }
+                    }
+                }
+            });
+        }
+        Err(e) => log::warn!("deserialize error: {}", e),
+    }
+}
+
+/// 交给单个应用处理，应用在 `scope` 内停用时跳过
+async fn deliver(app: &AppEntry, event: Arc<Event>, scope: Option<Scope>) -> Propagation {
+    if let Some(scope) = scope
+        && !switchboard::enabled(app.name(), scope, app.default_enabled())
+    {
+        return Propagation::Continue;
+    }
+    app.handle(event).await.unwrap_or_else(|e| {
+        log::warn!("app <{}> process error: {}", app.name(), e);
+        Propagation::Continue
+    })
 }
 
 pub async fn listener(
     pending_requests: Arc<DashMap<String, PendingRequest>>,
-    mut receiver: SplitStream<WsStream>,
+    transport: Arc<dyn Transport>,
 ) {
-    while let Some(msg) = receiver.next().await {
+    while let Some(frame) = transport.recv().await {
         *ROUND_START_TIME.lock().await = SystemTime::now();
-        match msg {
-            Ok(Message::Text(text)) => {
+        match frame {
+            Ok(text) => {
                 log::debug!("{}", text);
+                record::inbound(&text);
                 if let Ok(raw) = serde_json::from_str::<Value>(&text) {
                     if let Some(echo) = raw.get("echo").and_then(|v| v.as_str()) {
                         if let Some((_, pending)) = pending_requests.remove(echo) {
                             log::debug!("Session resume: {}", echo);
-                            match serde_json::from_value::<Response>(raw) {
-                                Ok(res) => _ = pending.sender.send(res),
-                                Err(e) => log::warn!("resp parse error: {}", e),
-                            }
+                            let res = serde_json::from_value::<Response>(raw).map_err(|e| {
+                                log::warn!("resp parse error: {}", e);
+                                ActionError::from(e)
+                            });
+                            _ = pending.sender.send(res);
                         } else {
                             log::warn!("Message received with unknown UUID: {}", echo);
                         }
                     } else {
-                        let event = serde_json::from_value::<Event>(raw);
-                        match event {
-                            Ok(event) => {
-                                let event = Arc::new(event);
-                                for app in APPS.iter() {
-                                    let event = event.clone();
-                                    tokio::spawn(async move {
-                                        let mut app = app.lock().await;
-                                        if let Err(e) = app.on_event(event).await {
-                                            log::warn!("app <{}> process error: {}", app.name(), e)
-                                        }
-                                    });
-                                }
-                            }
-                            Err(e) => log::warn!("deserialize error: {}", e),
-                        };
+                        dispatch(raw);
                     }
                 }
             }
-            Ok(Message::Close(_)) => {
-                log::warn!("connection closed.");
-                break;
-            }
             Err(e) => {
                 log::error!("listener: failed with error: {}", e);
-                break;
+                return;
             }
-            _ => log::warn!("unknown msg type: {:?}", unsafe { msg.unwrap_unchecked() }),
         }
     }
+    log::warn!("connection closed.");
 }
 
-async fn connect() -> Result<WsStream> {
-    log::info!("=> {}", config::ENDPOINT);
-    let url = format!("{}?access_token={}", config::ENDPOINT, config::TOKEN);
+/// 正向连接，返回首个生命周期事件中的 `self_id`
+pub(crate) async fn connect(endpoint: &str, token: &str) -> Result<(WsStream, Option<i64>)> {
+    log::info!("=> {}", endpoint);
+    let url = format!("{}?access_token={}", endpoint, token);
     let (mut ws, _) = connect_async(&url).await?;
     let msg = ws.next().await.ok_or(anyhow!("WebSocket stream ended"))??;
     let text = match msg {
@@ -107,33 +200,38 @@ async fn connect() -> Result<WsStream> {
         _ => return Err(anyhow!("Expected text message, check your endpoint")),
     };
     log::debug!("connect text: {}", text);
+    record::inbound(&text);
     let value = serde_json::from_str::<Value>(&text)?;
+    let self_id = value.get("self_id").and_then(|v| v.as_i64());
     if value.get("echo").is_some() {
         let res = serde_json::from_value::<Response>(value)?;
         if res.retcode != 200 {
             Err(anyhow!(format!("{:?}", res)))?
         }
     } else {
-        log::info!("Bot {} conncted!", value.get("self_id").unwrap_or_default());
+        log::info!("Bot {} conncted!", self_id.unwrap_or_default());
     }
-    Ok(ws)
+    Ok((ws, self_id))
 }
 
-async fn event_loop(ws: WsStream) -> Result<()> {
-    let (mut ws_sender, ws_receiver) = ws.split();
-    let (req_tx, mut req_rx) = mpsc::channel(5);
+/// 在任意传输上运行事件循环，直到连接断开
+///
+/// `self_id` 未知时通过 `get_login_info` 查询
+pub(crate) async fn event_loop(transport: impl Transport, self_id: Option<i64>) -> Result<()> {
+    let transport: Arc<dyn Transport> = Arc::new(transport);
+    let (req_tx, mut req_rx) = mpsc::channel::<Request>(5);
 
-    super::update(req_tx).await;
     let pending_requests = Arc::new(DashMap::new());
     let pending_requests_cloned = pending_requests.clone();
 
-    let task_event_listener = tokio::spawn(async move {
-        listener(pending_requests_cloned, ws_receiver).await;
+    let receiver = transport.clone();
+    let mut task_event_listener = tokio::spawn(async move {
+        listener(pending_requests_cloned, receiver).await;
     });
 
     // 定期清理过期请求的任务
     let pending_requests_cleanup = pending_requests.clone();
-    let cleanup_task = tokio::spawn(async move {
+    let mut cleanup_task = tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(30));
         loop {
             interval.tick().await;
@@ -150,30 +248,25 @@ async fn event_loop(ws: WsStream) -> Result<()> {
                 if let Some((_, expired_request)) = pending_requests_cleanup.remove(&key) {
                     log::warn!("Request {} expired and removed", key);
                     // 发送超时错误给等待的调用者
-                    let _ = expired_request.sender.send(Response {
-                        status: "timeout".to_string(),
-                        retcode: -1,
-                        data: None,
-                        message: Some("Request timeout".to_string()),
-                        echo: Some(key),
-                    });
+                    let _ = expired_request.sender.send(Err(ActionError::Timeout));
                 }
             }
         }
     });
 
-    let task_sender = tokio::spawn(async move {
+    let sender = transport.clone();
+    let mut task_sender = tokio::spawn(async move {
         while let Some(request) = req_rx.recv().await {
             log::debug!("request: {:?}", request.action);
-            if let Ok(message_str) = serde_json::to_string(&request) {
-                let message = Message::from(message_str);
+            if let Ok(frame) = serde_json::to_string(&request) {
+                record::outbound(&frame);
                 let pending = PendingRequest {
                     sender: request.sender,
                     created_at: request.created_at,
                 };
                 pending_requests.insert(request.echo.clone(), pending);
                 log::debug!("Session {} created", request.echo);
-                if let Err(e) = ws_sender.send(message).await {
+                if let Err(e) = sender.send(frame).await {
                     log::error!("RequestTask: failed with error: {}", e);
                 }
             } else {
@@ -182,42 +275,342 @@ async fn event_loop(ws: WsStream) -> Result<()> {
         }
     });
 
-    for app in APPS.iter() {
+    let bot = super::Protocol {
+        sender: Some(req_tx.clone()),
+    };
+    let self_id = match self_id {
+        Some(self_id) => Ok(self_id),
+        None => bot.get_login_info().await.map(|info| info.user_id),
+    };
+
+    if let Ok(self_id) = self_id {
+        super::update(self_id, req_tx.clone()).await;
+        application::connected(self_id).await;
+
+        tokio::select! {
+            _ = &mut task_event_listener => {
+                log::info!("Listener task endded");
+            }
+            _ = &mut task_sender => {
+                log::info!("Sender task endded");
+            }
+            _ = &mut cleanup_task => {
+                log::info!("Cleanup task endded");
+            }
+        }
+        if super::remove(self_id, &req_tx) {
+            application::disconnected(self_id).await;
+        }
+    }
+
+    task_event_listener.abort();
+    task_sender.abort();
+    cleanup_task.abort();
+    _ = transport.close().await;
+    self_id?;
+    Ok(())
+}
+
+/// 校验反向连接携带的 token，支持 `Authorization` 头与 `access_token` 参数
+fn authorized(req: &HandshakeRequest) -> bool {
+    let token = config::get().token.clone();
+    if token.is_empty() {
+        return true;
+    }
+    let header = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.trim_start_matches("Bearer ")
+                .trim_start_matches("Token ")
+                .to_string()
+        });
+    let query = req.uri().query().and_then(|q| {
+        q.split('&')
+            .find_map(|kv| kv.strip_prefix("access_token="))
+            .map(|v| v.to_string())
+    });
+    header.or(query).as_deref() == Some(token.as_str())
+}
+
+// 握手回调的签名由 tungstenite 决定
+#[allow(clippy::result_large_err)]
+async fn accept(stream: TcpStream) -> Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut self_id = None;
+    let callback = |req: &HandshakeRequest, res: HandshakeResponse| {
+        self_id = req
+            .headers()
+            .get("X-Self-ID")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<i64>().ok());
+        log::info!(
+            "Bot {} connecting from {}",
+            self_id.unwrap_or_default(),
+            peer
+        );
+        if authorized(req) {
+            Ok(res)
+        } else {
+            log::warn!("connection {} rejected: invalid access token", peer);
+            let mut err = ErrorResponse::new(Some("invalid access token".to_string()));
+            *err.status_mut() = StatusCode::UNAUTHORIZED;
+            Err(err)
+        }
+    };
+    let ws = accept_hdr_async(MaybeTlsStream::Plain(stream), callback).await?;
+    event_loop(WsTransport::new(ws), self_id).await
+}
+
+/// 反向 WebSocket，等待 OneBot 实现连入，每个连接独立运行事件循环
+async fn serve() -> Result<()> {
+    let addr = config::get().listen.clone();
+    let listener = TcpListener::bind(&addr).await?;
+    log::info!("<= {}", addr);
+    loop {
+        let (stream, peer) = listener.accept().await?;
         tokio::spawn(async move {
-            let mut app = app.lock().await;
-            if let Err(e) = app.on_load().await {
-                log::warn!("app <{}> on_load error: {}", app.name(), e)
+            if let Err(e) = accept(stream).await {
+                log::warn!("connection {} error: {}", peer, e);
             }
+            log::info!("connection {} closed", peer);
         });
     }
+}
+
+pub async fn launch() -> ! {
+    if config::get().mode == Mode::Replay {
+        let code = match replay::run(&config::get().replay).await {
+            Ok(()) => 0,
+            Err(e) => {
+                log::error!("replay error: {}", e);
+                1
+            }
+        };
+        application::shutdown().await;
+        std::process::exit(code);
+    }
 
-    tokio::select! {
-        _ = task_event_listener => {
-            log::info!("Listener task endded");
+    if config::get().mode != Mode::Forward {
+        loop {
+            let res = match config::get().mode {
+                #[cfg(feature = "http")]
+                Mode::Http => super::http::serve().await,
+                _ => serve().await,
+            };
+            if let Err(e) = res {
+                log::error!("serve error: {}", e);
+            }
+            log::info!("restarting server after 3s");
+            tokio::time::sleep(Duration::from_secs(3)).await;
         }
-        _ = task_sender => {
-            log::info!("Sender task endded");
+    }
+
+    reconcile_accounts();
+    loop {
+        forward(None, None).await;
+    }
+}
+
+/// 按当前配置的 `accounts` 启动新增账号的连接，停止已移除账号的连接
+///
+/// 重新加载配置后调用，非正向模式时不做任何事
+pub fn reconcile_accounts() {
+    let config = config::get();
+    if config.mode != Mode::Forward {
+        return;
+    }
+    let mut running = ACCOUNTS.lock().unwrap();
+    running.retain(|endpoint, stop| {
+        let keep = !stop.is_closed() && config.accounts.iter().any(|a| &a.endpoint == endpoint);
+        if !keep {
+            _ = stop.send(true);
         }
-        _ = cleanup_task => {
-            log::info!("Cleanup task endded");
+        keep
+    });
+    for account in &config.accounts {
+        if !running.contains_key(&account.endpoint) {
+            let (stop, stopped) = watch::channel(false);
+            running.insert(account.endpoint.clone(), stop);
+            tokio::spawn(forward(Some(account.endpoint.clone()), Some(stopped)));
         }
     }
-    Ok(())
 }
 
-pub async fn launch() -> ! {
+/// 等待 `stop` 被置为 `true`，`None` 时永不返回
+async fn stopped(stop: &mut Option<watch::Receiver<bool>>) {
+    match stop {
+        Some(stop) => _ = stop.wait_for(|stop| *stop).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 正向连接并自动重连，`account` 为 `accounts` 中账号的 `endpoint`，`None` 为主账号
+///
+/// 每次重连时重新读取配置；账号已不在配置中或收到 `stop` 时关闭连接并结束
+async fn forward(account: Option<String>, mut stop: Option<watch::Receiver<bool>>) {
     let mut retry: u32 = 0;
     loop {
-        match connect().await {
-            Ok(ws) => {
-                if let Err(e) = event_loop(ws).await {
+        let config = config::get();
+        let (endpoint, token) = match &account {
+            Some(endpoint) => match config.accounts.iter().find(|a| &a.endpoint == endpoint) {
+                Some(account) => (
+                    account.endpoint.as_str(),
+                    account.token.as_deref().unwrap_or(&config.token),
+                ),
+                None => break,
+            },
+            None => (config.endpoint.as_str(), config.token.as_str()),
+        };
+        let connected = tokio::select! {
+            res = connect(endpoint, token) => res,
+            _ = stopped(&mut stop) => break,
+        };
+        match connected {
+            Ok((ws, self_id)) => {
+                let transport = Arc::new(WsTransport::new(ws));
+                let run = event_loop(transport.clone(), self_id);
+                tokio::pin!(run);
+                let res = tokio::select! {
+                    res = &mut run => res,
+                    _ = stopped(&mut stop) => {
+                        // 关闭后监听任务收到对端的关闭帧，事件循环照常注销 bot
+                        _ = transport.close().await;
+                        _ = tokio::time::timeout(Duration::from_secs(5), run).await;
+                        break;
+                    }
+                };
+                if let Err(e) = res {
                     log::error!("loop error: {}", e);
                 }
             }
             Err(e) => log::warn!("launch error: {}", e),
         }
-        log::info!("reconnecting after 3s. times: {}", retry);
-        tokio::time::sleep(Duration::from_secs(3)).await;
+        log::info!("reconnecting {} after 3s. times: {}", endpoint, retry);
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(3)) => {}
+            _ = stopped(&mut stop) => break,
+        }
         retry += 1;
     }
+    if let Some(endpoint) = account {
+        log::info!("account {} stopped", endpoint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Response, reconcile_accounts};
+    use crate::{
+        config,
+        protocol::{bots, error::ActionError, mock::MockServer, response::SentMessage},
+    };
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::json;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+    /// 模拟额外账号的 OneBot 实现，连入后上报生命周期事件，直到对端关闭，同时返回连接次数
+    async fn account(self_id: i64) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("ws://{}/", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::Relaxed);
+                let Ok(mut ws) = accept_async(stream).await else {
+                    continue;
+                };
+                let lifecycle = json!({
+                    "time": 0,
+                    "self_id": self_id,
+                    "post_type": "meta_event",
+                    "meta_event_type": "lifecycle",
+                    "sub_type": "connect",
+                });
+                _ = ws.send(Message::from(lifecycle.to_string())).await;
+                while let Some(Ok(frame)) = ws.next().await {
+                    if frame.is_close() {
+                        break;
+                    }
+                }
+            }
+        });
+        (endpoint, connections)
+    }
+
+    fn load(endpoints: &[&str]) {
+        let mut text = "token = \"\"\nowner = 1\n".to_string();
+        for endpoint in endpoints {
+            text += &format!("[[accounts]]\nendpoint = {:?}\n", endpoint);
+        }
+        config::load_str(&text).unwrap();
+        reconcile_accounts();
+    }
+
+    async fn wait_until(f: impl Fn(&[i64]) -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !f(&bots()) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("accounts not reconciled");
+    }
+
+    #[tokio::test]
+    async fn reload_reconciles_accounts_by_endpoint() {
+        let _server = MockServer::start(10012, "token = \"\"\nowner = 1\n").await;
+        let (a, a_connections) = account(20001).await;
+        let (b, _) = account(20002).await;
+
+        load(&[&a]);
+        wait_until(|bots| bots.contains(&20001)).await;
+
+        // a 移到第二位不影响其连接，新增的 b 被启动
+        load(&[&b, &a]);
+        wait_until(|bots| bots.contains(&20002)).await;
+        assert!(bots().contains(&20001));
+        assert_eq!(a_connections.load(Ordering::Relaxed), 1);
+
+        load(&[&b]);
+        wait_until(|bots| !bots.contains(&20001)).await;
+        assert!(bots().contains(&20002));
+
+        load(&[]);
+        wait_until(|bots| !bots.contains(&20002)).await;
+        assert!(bots().contains(&10012));
+    }
+
+    #[test]
+    fn async_responses_have_no_data() {
+        let response = |v: serde_json::Value| serde_json::from_value::<Response>(v).unwrap();
+        let accepted = json!({ "status": "async", "retcode": 1, "data": null });
+        let sent = response(accepted.clone())
+            .into_data::<SentMessage>()
+            .unwrap();
+        assert!(sent.is_none());
+        let ok = json!({ "status": "ok", "retcode": 0, "data": { "message_id": 3 } });
+        let sent = response(ok).into_data::<SentMessage>().unwrap();
+        assert_eq!(sent.unwrap().message_id, 3);
+        let missing = json!({ "status": "ok", "retcode": 0, "data": null });
+        assert!(matches!(
+            response(missing).into_data::<SentMessage>(),
+            Err(ActionError::Deserialize(_))
+        ));
+        let failed = json!({ "status": "failed", "retcode": 100, "data": null });
+        assert!(matches!(
+            response(failed).into_data::<SentMessage>(),
+            Err(ActionError::Failed { retcode: 100, .. })
+        ));
+    }
 }
@@ -0,0 +1,80 @@
+use crate::protocol::message::MediaTooLarge;
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, ActionError>;
+
+/// 动作调用错误
+#[derive(Debug)]
+#[allow(unused)]
+pub enum ActionError {
+    /// 尚未连接到 OneBot 实现
+    NotConnected,
+    /// 超时未收到响应
+    Timeout,
+    /// 连接已断开，请求或响应通道关闭
+    ChannelClosed,
+    /// OneBot 实现返回失败
+    Failed {
+        retcode: i32,
+        message: Option<String>,
+    },
+    /// 实现以 `async` 受理了需要返回数据的动作，没有数据
+    NoData,
+    /// 响应数据反序列化失败
+    Deserialize(serde_json::Error),
+    /// 本地校验未通过，请求没有发出，不是 OneBot 实现的失败
+    Invalid(MediaTooLarge),
+}
+
+impl ActionError {
+    /// 是否值得重试，连接类错误可重试，实现返回的失败（如权限不足）与本地校验失败不应重试
+    #[allow(unused)]
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ActionError::NotConnected | ActionError::Timeout | ActionError::ChannelClosed
+        )
+    }
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::NotConnected => write!(f, "sender not found"),
+            ActionError::Timeout => write!(f, "request timeout"),
+            ActionError::ChannelClosed => write!(f, "channel closed"),
+            ActionError::Failed { retcode, message } => {
+                write!(f, "action failed ({})", retcode)?;
+                if let Some(message) = message {
+                    write!(f, ": {}", message)?;
+                }
+                Ok(())
+            }
+            ActionError::NoData => write!(f, "accepted asynchronously without data"),
+            ActionError::Deserialize(e) => write!(f, "deserialize error: {}", e),
+            ActionError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ActionError::Deserialize(e) => Some(e),
+            ActionError::Invalid(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<MediaTooLarge> for ActionError {
+    fn from(e: MediaTooLarge) -> Self {
+        ActionError::Invalid(e)
+    }
+}
+
+impl From<serde_json::Error> for ActionError {
+    fn from(e: serde_json::Error) -> Self {
+        ActionError::Deserialize(e)
+    }
+}
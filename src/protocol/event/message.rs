@@ -1,20 +1,20 @@
 use super::EventBase;
 use crate::protocol::{
-    adapter::Response,
-    get_bot,
+    error::Result,
+    get_bot_by_id,
     message::{self, Message},
+    response::SentMessage,
 };
-use anyhow::Result;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "message_type", rename_all = "snake_case")]
 pub enum MessageEvent {
     Private(PrivateMessage),
     Group(GroupMessage),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[allow(unused)]
 pub struct PrivateMessage {
     #[serde(flatten)]
@@ -28,7 +28,7 @@ pub struct PrivateMessage {
     pub sender: PrivateSender,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[allow(unused)]
 pub enum PrivateMessageType {
@@ -37,7 +37,7 @@ pub enum PrivateMessageType {
     Other,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[allow(unused)]
 pub struct PrivateSender {
     pub user_id: i64,
@@ -46,7 +46,7 @@ pub struct PrivateSender {
     pub age: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[allow(unused)]
 pub struct GroupMessage {
     #[serde(flatten)]
@@ -62,7 +62,7 @@ pub struct GroupMessage {
     pub sender: GroupSender,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[allow(unused)]
 pub enum GroupMessageType {
@@ -71,7 +71,7 @@ pub enum GroupMessageType {
     Notice,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[allow(unused)]
 pub struct GroupSender {
     pub user_id: i64,
@@ -85,7 +85,7 @@ pub struct GroupSender {
     pub title: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[allow(unused)]
 pub enum GroupRole {
@@ -94,7 +94,7 @@ pub enum GroupRole {
     Member,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[allow(unused)]
 pub struct Anonymous {
     pub id: i64,
@@ -104,21 +104,15 @@ pub struct Anonymous {
 
 impl PrivateMessage {
     #[allow(unused)]
-    pub async fn reply<T>(&self, message: T, quote: bool) -> Result<Response>
+    pub async fn reply<T>(&self, message: T, quote: bool) -> Result<Option<SentMessage>>
     where
         T: Into<Message>,
     {
         let mut message = message.into();
         if quote {
-            message.insert(
-                0,
-                message::Segment::Reply {
-                    id: self.message_id.to_string(),
-                },
-            );
+            message = message.reply_to(self.message_id);
         }
-        get_bot()
-            .await
+        get_bot_by_id(self.base.self_id)
             .send_private_message(self.user_id, message)
             .await
     }
@@ -126,27 +120,33 @@ impl PrivateMessage {
 
 impl GroupMessage {
     #[allow(unused)]
-    pub async fn reply<T>(&self, message: T, quote: bool) -> Result<Response>
+    pub async fn reply<T>(&self, message: T, quote: bool) -> Result<Option<SentMessage>>
     where
         T: Into<Message>,
     {
         let mut message = message.into();
         if quote {
-            message.insert(
-                0,
-                message::Segment::Reply {
-                    id: self.message_id.to_string(),
-                },
-            );
+            message = message.reply_to(self.message_id);
         }
-        get_bot()
-            .await
+        get_bot_by_id(self.base.self_id)
             .send_group_message(self.group_id, message)
             .await
     }
 }
 
 impl MessageEvent {
+    pub fn base(&self) -> &EventBase {
+        match self {
+            MessageEvent::Group(x) => &x.base,
+            MessageEvent::Private(x) => &x.base,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn self_id(&self) -> i64 {
+        self.base().self_id
+    }
+
     #[allow(unused)]
     pub fn message_id(&self) -> i32 {
         match self {
@@ -180,20 +180,15 @@ impl MessageEvent {
     }
 
     #[allow(unused)]
-    pub async fn reply<T>(&self, message: T, quote: bool) -> Result<Response>
+    pub async fn reply<T>(&self, message: T, quote: bool) -> Result<Option<SentMessage>>
     where
         T: Into<Message>,
     {
         let mut message = message.into();
         if quote {
-            message.insert(
-                0,
-                message::Segment::Reply {
-                    id: self.message_id().to_string(),
-                },
-            );
+            message = message.reply_to(self.message_id());
         }
-        let bot = get_bot().await;
+        let bot = get_bot_by_id(self.self_id());
         match self {
             MessageEvent::Group(x) => bot.send_group_message(x.group_id, message).await,
             MessageEvent::Private(x) => bot.send_private_message(x.user_id, message).await,
@@ -1,13 +1,49 @@
 use super::EventBase;
-use serde::Deserialize;
+use crate::protocol::tagged;
+use serde::{Deserialize, Deserializer};
 use serde_json::Value;
 
 #[derive(Debug, Deserialize)]
 #[allow(unused)]
-#[serde(tag = "meta_event_type", rename_all = "lowercase")]
+#[serde(remote = "Self", tag = "meta_event_type", rename_all = "lowercase")]
 pub enum MetaEvent {
     LifeCycle(LifeCycle),
     HeartBeat(HeartBeat),
+    /// 未识别的元事件，保留原始字段
+    #[serde(skip)]
+    Unknown {
+        kind: String,
+        base: EventBase,
+        data: Value,
+    },
+}
+
+impl<'de> Deserialize<'de> for MetaEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        tagged::deserialize(
+            deserializer,
+            "meta_event_type",
+            &["lifecycle", "heartbeat"],
+            MetaEvent::deserialize,
+            |kind, data| {
+                Ok(MetaEvent::Unknown {
+                    kind,
+                    base: EventBase::deserialize(&data)?,
+                    data,
+                })
+            },
+        )
+    }
+}
+
+impl MetaEvent {
+    pub fn base(&self) -> &EventBase {
+        match self {
+            MetaEvent::LifeCycle(x) => &x.base,
+            MetaEvent::HeartBeat(x) => &x.base,
+            MetaEvent::Unknown { base, .. } => base,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
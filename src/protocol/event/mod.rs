@@ -1,11 +1,24 @@
-use serde::Deserialize;
+use crate::protocol::{
+    message::{Message, Segment},
+    tagged,
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 mod message;
 mod meta;
 mod notice;
 mod request;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[allow(unused)]
 pub struct EventBase {
     pub time: i64,
@@ -22,8 +35,8 @@ pub use notice::*;
 pub use request::*;
 
 #[derive(Debug, Deserialize)]
-#[serde(tag = "post_type", rename_all = "snake_case")]
-#[allow(unused)]
+#[serde(remote = "Self", tag = "post_type", rename_all = "snake_case")]
+#[allow(unused, clippy::large_enum_variant, clippy::enum_variant_names)]
 pub enum Event {
     #[serde(rename = "message")]
     MessageEvent(message::MessageEvent),
@@ -31,4 +44,213 @@ pub enum Event {
     #[serde(rename = "request")]
     RequestEvent(request::Request),
     MetaEvent(meta::MetaEvent),
+    /// 未识别的事件，保留原始字段
+    #[serde(skip)]
+    Unknown {
+        kind: String,
+        base: EventBase,
+        data: Value,
+    },
+}
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        tagged::deserialize(
+            deserializer,
+            "post_type",
+            &["message", "notice", "request", "meta_event"],
+            Event::deserialize,
+            |kind, data| {
+                Ok(Event::Unknown {
+                    kind,
+                    base: EventBase::deserialize(&data)?,
+                    data,
+                })
+            },
+        )
+    }
+}
+
+/// 事件类型，对应 `post_type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Message,
+    Notice,
+    Request,
+    Meta,
+    /// 未识别的 `post_type`
+    Unknown,
+}
+
+impl EventKind {
+    pub const ALL: &'static [EventKind] = &[
+        EventKind::Message,
+        EventKind::Notice,
+        EventKind::Request,
+        EventKind::Meta,
+        EventKind::Unknown,
+    ];
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::MessageEvent(_) => EventKind::Message,
+            Event::Notice(_) => EventKind::Notice,
+            Event::RequestEvent(_) => EventKind::Request,
+            Event::MetaEvent(_) => EventKind::Meta,
+            Event::Unknown { .. } => EventKind::Unknown,
+        }
+    }
+
+    /// 事件公共字段，`self_id` 为收到事件的账号
+    #[allow(unused)]
+    pub fn base(&self) -> &EventBase {
+        match self {
+            Event::MessageEvent(e) => e.base(),
+            Event::Notice(e) => e.base(),
+            Event::RequestEvent(e) => e.base(),
+            Event::MetaEvent(e) => e.base(),
+            Event::Unknown { base, .. } => base,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn self_id(&self) -> i64 {
+        self.base().self_id
+    }
+
+    /// 事件中未识别的部分，如 `notice <input_status>`、`segment <mface>`
+    pub fn unknown_parts(&self) -> Vec<String> {
+        match self {
+            Event::MessageEvent(e) => {
+                let mut parts = Vec::new();
+                unknown_segments(e.message(), &mut parts);
+                parts
+            }
+            Event::Notice(Notice::Notify(NotifyEvent::Unknown { kind, .. })) => {
+                vec![format!("notify <{}>", kind)]
+            }
+            Event::Notice(Notice::Unknown { kind, .. }) => vec![format!("notice <{}>", kind)],
+            Event::RequestEvent(Request::Unknown { kind, .. }) => {
+                vec![format!("request <{}>", kind)]
+            }
+            Event::MetaEvent(MetaEvent::Unknown { kind, .. }) => {
+                vec![format!("meta_event <{}>", kind)]
+            }
+            Event::Unknown { kind, .. } => vec![format!("event <{}>", kind)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn unknown_segments(message: &Message, parts: &mut Vec<String>) {
+    for segment in message {
+        match segment {
+            Segment::Unknown { kind, .. } => parts.push(format!("segment <{}>", kind)),
+            Segment::Node {
+                content: Some(content),
+                ..
+            } => unknown_segments(content, parts),
+            _ => {}
+        }
+    }
+}
+
+static UNKNOWN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref UNKNOWN_SEEN: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// 统计事件中未识别的部分，每种只警告一次，事件照常分发
+pub fn report_unknown(event: &Event) {
+    for part in event.unknown_parts() {
+        UNKNOWN_COUNT.fetch_add(1, Ordering::Relaxed);
+        if UNKNOWN_SEEN.lock().unwrap().insert(part.clone()) {
+            log::warn!("unrecognized {}, passed through as unknown", part);
+        } else {
+            log::debug!("unrecognized {}", part);
+        }
+    }
+}
+
+/// 启动以来遇到的未识别事件、通知与消息段数
+pub fn unknown_count() -> usize {
+    UNKNOWN_COUNT.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, MetaEvent, Notice, NotifyEvent, unknown_count};
+    use crate::protocol::{message::Segment, mock::group_message};
+    use serde_json::{Value, json};
+
+    fn event(mut raw: Value) -> Event {
+        raw["time"] = 0.into();
+        raw["self_id"] = 10.into();
+        serde_json::from_value(raw).unwrap()
+    }
+
+    #[test]
+    fn unknown_payloads_are_kept() {
+        let notice =
+            event(json!({ "post_type": "notice", "notice_type": "input_status", "user_id": 1 }));
+        assert!(matches!(
+            &notice,
+            Event::Notice(Notice::Unknown { kind, data, .. }) if kind == "input_status" && data["user_id"] == 1
+        ));
+        assert_eq!(notice.self_id(), 10);
+        let notify = event(
+            json!({ "post_type": "notice", "notice_type": "notify", "sub_type": "profile_like" }),
+        );
+        assert!(matches!(
+            notify,
+            Event::Notice(Notice::Notify(NotifyEvent::Unknown { .. }))
+        ));
+        let meta = event(
+            json!({ "post_type": "meta_event", "meta_event_type": "heartbeat", "status": {} }),
+        );
+        assert!(matches!(meta, Event::MetaEvent(MetaEvent::HeartBeat(_))));
+        let other = event(json!({ "post_type": "message_sent" }));
+        assert_eq!(other.unknown_parts(), ["event <message_sent>"]);
+
+        let mut raw = group_message(1, 2, "");
+        raw["message_id"] = 0.into();
+        raw["message"] = json!([
+            { "type": "text", "data": { "text": "看" } },
+            { "type": "keyboard", "data": { "content": {} } },
+        ]);
+        let message = event(raw);
+        let Event::MessageEvent(e) = &message else {
+            unreachable!()
+        };
+        assert!(matches!(&e.message()[1], Segment::Unknown { kind, .. } if kind == "keyboard"));
+        assert_eq!(format!("{}", e.message()), "看 [keyboard] ");
+
+        let before = unknown_count();
+        super::report_unknown(&message);
+        super::report_unknown(&message);
+        assert!(unknown_count() >= before + 2);
+    }
+
+    #[test]
+    fn malformed_known_payloads_keep_their_error() {
+        let mut raw = group_message(1, 2, "");
+        raw["time"] = 0.into();
+        raw["self_id"] = 10.into();
+        raw["message_id"] = "not a number".into();
+        assert!(serde_json::from_value::<Event>(raw.clone()).is_err());
+
+        raw["message_id"] = 0.into();
+        raw["message"] = json!([{ "type": "image", "data": { "url": "https://x/a.png" } }]);
+        let err = serde_json::from_value::<Event>(raw).unwrap_err();
+        assert!(err.to_string().contains("file"), "{}", err);
+
+        let notice =
+            json!({ "post_type": "notice", "notice_type": "group_ban", "time": 0, "self_id": 10 });
+        assert!(serde_json::from_value::<Event>(notice).is_err());
+        let segment = json!({ "type": "at", "data": {} });
+        assert!(serde_json::from_value::<Segment>(segment).is_err());
+    }
 }
@@ -1,8 +1,25 @@
 use super::EventBase;
-use serde::Deserialize;
+use crate::protocol::tagged;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// `Notice` 已定义的 `notice_type`
+const KNOWN_NOTICES: &[&str] = &[
+    "group_upload",
+    "group_admin",
+    "group_decrease",
+    "group_increase",
+    "group_ban",
+    "friend_add",
+    "group_recall",
+    "friend_recall",
+    "notify",
+    #[cfg(feature = "napcat")]
+    "group_card",
+];
 
 #[derive(Debug, Deserialize)]
-#[serde(tag = "notice_type", rename_all = "snake_case")]
+#[serde(remote = "Self", tag = "notice_type", rename_all = "snake_case")]
 #[allow(unused)]
 pub enum Notice {
     GroupUpload(GroupUploadNotice),
@@ -17,6 +34,51 @@ pub enum Notice {
 
     #[cfg(feature = "napcat")]
     GroupCard(GroupCardNotice),
+
+    /// 未识别的通知，保留原始字段
+    #[serde(skip)]
+    Unknown {
+        kind: String,
+        base: EventBase,
+        data: Value,
+    },
+}
+
+impl<'de> Deserialize<'de> for Notice {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        tagged::deserialize(
+            deserializer,
+            "notice_type",
+            KNOWN_NOTICES,
+            Notice::deserialize,
+            |kind, data| {
+                Ok(Notice::Unknown {
+                    kind,
+                    base: EventBase::deserialize(&data)?,
+                    data,
+                })
+            },
+        )
+    }
+}
+
+impl Notice {
+    pub fn base(&self) -> &EventBase {
+        match self {
+            Notice::GroupUpload(x) => &x.base,
+            Notice::GroupAdmin(x) => &x.base,
+            Notice::GroupDecrease(x) => &x.base,
+            Notice::GroupIncrease(x) => &x.base,
+            Notice::GroupBan(x) => &x.base,
+            Notice::FriendAdd(x) => &x.base,
+            Notice::GroupRecall(x) => &x.base,
+            Notice::FriendRecall(x) => &x.base,
+            Notice::Notify(x) => x.base(),
+            #[cfg(feature = "napcat")]
+            Notice::GroupCard(x) => &x.base,
+            Notice::Unknown { base, .. } => base,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -144,12 +206,48 @@ pub struct FriendRecallNotice {
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(tag = "sub_type", rename_all = "snake_case")]
+#[serde(remote = "Self", tag = "sub_type", rename_all = "snake_case")]
 #[allow(unused)]
 pub enum NotifyEvent {
     Poke(PokeNotify),
     LuckyKing(LuckyKingNotify),
     Honor(HonorNotify),
+    /// 未识别的提醒，保留原始字段
+    #[serde(skip)]
+    Unknown {
+        kind: String,
+        base: EventBase,
+        data: Value,
+    },
+}
+
+impl<'de> Deserialize<'de> for NotifyEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        tagged::deserialize(
+            deserializer,
+            "sub_type",
+            &["poke", "lucky_king", "honor"],
+            NotifyEvent::deserialize,
+            |kind, data| {
+                Ok(NotifyEvent::Unknown {
+                    kind,
+                    base: EventBase::deserialize(&data)?,
+                    data,
+                })
+            },
+        )
+    }
+}
+
+impl NotifyEvent {
+    pub fn base(&self) -> &EventBase {
+        match self {
+            NotifyEvent::Poke(x) => &x.base,
+            NotifyEvent::LuckyKing(x) => &x.base,
+            NotifyEvent::Honor(x) => &x.base,
+            NotifyEvent::Unknown { base, .. } => base,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -1,12 +1,49 @@
 use super::EventBase;
-use serde::Deserialize;
+use crate::protocol::tagged;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
 
 #[derive(Debug, Deserialize)]
-#[serde(tag = "request_type", rename_all = "snake_case")]
+#[serde(remote = "Self", tag = "request_type", rename_all = "snake_case")]
 #[allow(unused)]
 pub enum Request {
     Friend(FriendRequest),
     Group(GroupRequest),
+    /// 未识别的请求，保留原始字段
+    #[serde(skip)]
+    Unknown {
+        kind: String,
+        base: EventBase,
+        data: Value,
+    },
+}
+
+impl<'de> Deserialize<'de> for Request {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        tagged::deserialize(
+            deserializer,
+            "request_type",
+            &["friend", "group"],
+            Request::deserialize,
+            |kind, data| {
+                Ok(Request::Unknown {
+                    kind,
+                    base: EventBase::deserialize(&data)?,
+                    data,
+                })
+            },
+        )
+    }
+}
+
+impl Request {
+    pub fn base(&self) -> &EventBase {
+        match self {
+            Request::Friend(x) => &x.base,
+            Request::Group(x) => &x.base,
+            Request::Unknown { base, .. } => base,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
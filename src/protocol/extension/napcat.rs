@@ -1,8 +1,7 @@
 // https://napneko.github.io
 // https://napcat.apifox.cn
 
-use crate::protocol::{Protocol, adapter::Response, message::Message};
-use anyhow::Result;
+use crate::protocol::{Protocol, error::Result, message::Message, response::SentForward};
 use serde_json::json;
 
 impl Protocol {
@@ -11,7 +10,7 @@ impl Protocol {
     /// # 参数
     ///
     /// * `message_type` - 消息类型，支持 `private`、`group`，分别对应私聊、群组，
-    ///                   如不传入，则根据传入的 `*_id` 参数判断
+    ///   如不传入，则根据传入的 `*_id` 参数判断
     /// * `user_id` - 对方 QQ 号（消息类型为 `private` 时需要）
     /// * `group_id` - 群号（消息类型为 `group` 时需要）
     /// * `message` - 消息,需要是 node[], 详见 node
@@ -26,11 +25,13 @@ impl Protocol {
         user_id: Option<i64>,
         group_id: Option<i64>,
         message: T,
-    ) -> Result<Response>
+    ) -> Result<Option<SentForward>>
     where
         T: Into<Message>,
     {
-        let mut data = json!({"message": message.into()});
+        let message = message.into();
+        message.check_media_size()?;
+        let mut data = json!({"message": message});
         if let Some(msg_type) = message_type {
             data["message_type"] = json!(msg_type);
         }
@@ -40,6 +41,6 @@ impl Protocol {
         if let Some(gid) = group_id {
             data["group_id"] = json!(gid);
         }
-        self.send_request("send_msg", data).await
+        self.call_optional("send_msg", data).await
     }
 }
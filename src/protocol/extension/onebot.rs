@@ -1,5 +1,4 @@
-use crate::protocol::{Protocol, Response, message::Message};
-use anyhow::Result;
+use crate::protocol::{Protocol, error::Result, message::Message, response::*};
 use serde_json::{Value, json};
 
 #[allow(unused)]
@@ -15,15 +14,20 @@ impl Protocol {
     ///
     /// # 响应数据
     ///
-    /// * `message_id` - 消息 ID (number int32)
-    pub async fn send_private_message<T>(&self, user_id: i64, message: T) -> Result<Response>
+    /// * `message_id` - 消息 ID (number int32)，实现以 `async` 受理时为 `None`
+    pub async fn send_private_message<T>(
+        &self,
+        user_id: i64,
+        message: T,
+    ) -> Result<Option<SentMessage>>
     where
         T: Into<Message>,
     {
         let message = message.into();
+        message.check_media_size()?;
         log::info!("User({}) <- {}", user_id, message);
         let data = json!({"user_id": user_id, "message": message});
-        self.send_request("send_private_msg", data).await
+        self.call_optional("send_private_msg", data).await
     }
 
     /// 发送群消息
@@ -35,15 +39,20 @@ impl Protocol {
     ///
     /// # 响应数据
     ///
-    /// * `message_id` - 消息 ID (number int32)
-    pub async fn send_group_message<T>(&self, group_id: i64, message: T) -> Result<Response>
+    /// * `message_id` - 消息 ID (number int32)，实现以 `async` 受理时为 `None`
+    pub async fn send_group_message<T>(
+        &self,
+        group_id: i64,
+        message: T,
+    ) -> Result<Option<SentMessage>>
     where
         T: Into<Message>,
     {
         let message = message.into();
+        message.check_media_size()?;
         log::info!("Group({}) <- {}", group_id, message);
         let data = json!({"group_id": group_id, "message": message});
-        self.send_request("send_group_msg", data).await
+        self.call_optional("send_group_msg", data).await
     }
 
     /// 发送消息
@@ -51,25 +60,26 @@ impl Protocol {
     /// # 参数
     ///
     /// * `message_type` - 消息类型，支持 `private`、`group`，分别对应私聊、群组，
-    ///                   如不传入，则根据传入的 `*_id` 参数判断
+    ///   如不传入，则根据传入的 `*_id` 参数判断
     /// * `user_id` - 对方 QQ 号（消息类型为 `private` 时需要）
     /// * `group_id` - 群号（消息类型为 `group` 时需要）
     /// * `message` - 要发送的内容
     ///
     /// # 响应数据
     ///
-    /// * `message_id` - 消息 ID (number int32)
+    /// * `message_id` - 消息 ID (number int32)，实现以 `async` 受理时为 `None`
     pub async fn send_message<T>(
         &self,
         message_type: Option<&str>,
         user_id: Option<i64>,
         group_id: Option<i64>,
         message: T,
-    ) -> Result<Response>
+    ) -> Result<Option<SentMessage>>
     where
         T: Into<Message>,
     {
         let message = message.into();
+        message.check_media_size()?;
         match (message_type, user_id, group_id) {
             (Some("private"), Some(uid), _) | (None, Some(uid), None) => {
                 log::info!("User({}) <- {}", uid, message);
@@ -92,7 +102,7 @@ impl Protocol {
         if let Some(gid) = group_id {
             data["group_id"] = json!(gid);
         }
-        self.send_request("send_msg", data).await
+        self.call_optional("send_msg", data).await
     }
 
     /// 撤回消息
@@ -104,9 +114,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn delete_message(&self, message_id: i32) -> Result<Response> {
+    pub async fn delete_message(&self, message_id: i32) -> Result<()> {
         let data = json!({"message_id": message_id});
-        self.send_request("delete_msg", data).await
+        self.call_unit("delete_msg", data).await
     }
 
     /// 获取消息
@@ -123,9 +133,9 @@ impl Protocol {
     /// * `real_id` - 消息真实 ID (number int32)
     /// * `sender` - 发送人信息，同消息事件
     /// * `message` - 消息内容
-    pub async fn get_message(&self, message_id: i32) -> Result<Response> {
+    pub async fn get_message(&self, message_id: i32) -> Result<MessageDetail> {
         let data = json!({"message_id": message_id});
-        self.send_request("get_msg", data).await
+        self.call("get_msg", data).await
     }
 
     /// 获取合并转发消息
@@ -137,9 +147,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// * `message` - 消息内容，使用消息的数组格式表示，数组中的消息段全部为 `node` 消息段
-    pub async fn get_forward_message(&self, id: &str) -> Result<Response> {
+    pub async fn get_forward_message(&self, id: &str) -> Result<ForwardMessage> {
         let data = json!({"id": id});
-        self.send_request("get_forward_msg", data).await
+        self.call("get_forward_msg", data).await
     }
 
     // 好友相关 API
@@ -154,9 +164,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn send_like(&self, user_id: i64, times: i32) -> Result<Response> {
+    pub async fn send_like(&self, user_id: i64, times: i32) -> Result<()> {
         let data = json!({"user_id": user_id, "times": times});
-        self.send_request("send_like", data).await
+        self.call_unit("send_like", data).await
     }
 
     // 群组管理 API
@@ -177,9 +187,9 @@ impl Protocol {
         group_id: i64,
         user_id: i64,
         reject_add_request: bool,
-    ) -> Result<Response> {
+    ) -> Result<()> {
         let data = json!({"group_id": group_id, "user_id": user_id, "reject_add_request": reject_add_request});
-        self.send_request("set_group_kick", data).await
+        self.call_unit("set_group_kick", data).await
     }
 
     /// 群组单人禁言
@@ -193,14 +203,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn set_group_ban(
-        &self,
-        group_id: i64,
-        user_id: i64,
-        duration: i32,
-    ) -> Result<Response> {
+    pub async fn set_group_ban(&self, group_id: i64, user_id: i64, duration: i32) -> Result<()> {
         let data = json!({"group_id": group_id, "user_id": user_id, "duration": duration});
-        self.send_request("set_group_ban", data).await
+        self.call_unit("set_group_ban", data).await
     }
 
     /// 群组匿名用户禁言
@@ -225,7 +230,7 @@ impl Protocol {
         anonymous: Option<Value>,
         anonymous_flag: Option<&str>,
         duration: i32,
-    ) -> Result<Response> {
+    ) -> Result<()> {
         let mut data = json!({"group_id": group_id, "duration": duration});
         if let Some(anon) = anonymous {
             data["anonymous"] = anon;
@@ -233,7 +238,7 @@ impl Protocol {
         if let Some(flag) = anonymous_flag {
             data["anonymous_flag"] = json!(flag);
         }
-        self.send_request("set_group_anonymous_ban", data).await
+        self.call_unit("set_group_anonymous_ban", data).await
     }
 
     /// 群组全员禁言
@@ -246,9 +251,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn set_group_whole_ban(&self, group_id: i64, enable: bool) -> Result<Response> {
+    pub async fn set_group_whole_ban(&self, group_id: i64, enable: bool) -> Result<()> {
         let data = json!({"group_id": group_id, "enable": enable});
-        self.send_request("set_group_whole_ban", data).await
+        self.call_unit("set_group_whole_ban", data).await
     }
 
     /// 群组设置管理员
@@ -262,14 +267,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn set_group_admin(
-        &self,
-        group_id: i64,
-        user_id: i64,
-        enable: bool,
-    ) -> Result<Response> {
+    pub async fn set_group_admin(&self, group_id: i64, user_id: i64, enable: bool) -> Result<()> {
         let data = json!({"group_id": group_id, "user_id": user_id, "enable": enable});
-        self.send_request("set_group_admin", data).await
+        self.call_unit("set_group_admin", data).await
     }
 
     /// 群组匿名
@@ -282,9 +282,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn set_group_anonymous(&self, group_id: i64, enable: bool) -> Result<Response> {
+    pub async fn set_group_anonymous(&self, group_id: i64, enable: bool) -> Result<()> {
         let data = json!({"group_id": group_id, "enable": enable});
-        self.send_request("set_group_anonymous", data).await
+        self.call_unit("set_group_anonymous", data).await
     }
 
     /// 设置群名片（群备注）
@@ -298,14 +298,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn set_group_card(
-        &self,
-        group_id: i64,
-        user_id: i64,
-        card: &str,
-    ) -> Result<Response> {
+    pub async fn set_group_card(&self, group_id: i64, user_id: i64, card: &str) -> Result<()> {
         let data = json!({"group_id": group_id, "user_id": user_id, "card": card});
-        self.send_request("set_group_card", data).await
+        self.call_unit("set_group_card", data).await
     }
 
     /// 设置群名
@@ -318,9 +313,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn set_group_name(&self, group_id: i64, group_name: &str) -> Result<Response> {
+    pub async fn set_group_name(&self, group_id: i64, group_name: &str) -> Result<()> {
         let data = json!({"group_id": group_id, "group_name": group_name});
-        self.send_request("set_group_name", data).await
+        self.call_unit("set_group_name", data).await
     }
 
     /// 退出群组
@@ -333,9 +328,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn set_group_leave(&self, group_id: i64, is_dismiss: bool) -> Result<Response> {
+    pub async fn set_group_leave(&self, group_id: i64, is_dismiss: bool) -> Result<()> {
         let data = json!({"group_id": group_id, "is_dismiss": is_dismiss});
-        self.send_request("set_group_leave", data).await
+        self.call_unit("set_group_leave", data).await
     }
 
     /// 设置群组专属头衔
@@ -360,9 +355,9 @@ impl Protocol {
         user_id: i64,
         special_title: &str,
         duration: i32,
-    ) -> Result<Response> {
+    ) -> Result<()> {
         let data = json!({"group_id": group_id, "user_id": user_id, "special_title": special_title, "duration": duration});
-        self.send_request("set_group_special_title", data).await
+        self.call_unit("set_group_special_title", data).await
     }
 
     // 请求处理 API
@@ -383,9 +378,9 @@ impl Protocol {
         flag: &str,
         approve: bool,
         remark: &str,
-    ) -> Result<Response> {
+    ) -> Result<()> {
         let data = json!({"flag": flag, "approve": approve, "remark": remark});
-        self.send_request("set_friend_add_request", data).await
+        self.call_unit("set_friend_add_request", data).await
     }
 
     /// 处理加群请求／邀请
@@ -406,10 +401,10 @@ impl Protocol {
         sub_type: &str,
         approve: bool,
         reason: &str,
-    ) -> Result<Response> {
+    ) -> Result<()> {
         let data =
             json!({"flag": flag, "sub_type": sub_type, "approve": approve, "reason": reason});
-        self.send_request("set_group_add_request", data).await
+        self.call_unit("set_group_add_request", data).await
     }
 
     // 信息获取 API
@@ -424,8 +419,8 @@ impl Protocol {
     ///
     /// * `user_id` - QQ 号 (number int64)
     /// * `nickname` - QQ 昵称
-    pub async fn get_login_info(&self) -> Result<Response> {
-        self.send_request("get_login_info", json!({})).await
+    pub async fn get_login_info(&self) -> Result<LoginInfo> {
+        self.call("get_login_info", json!({})).await
     }
 
     /// 获取陌生人信息
@@ -441,9 +436,9 @@ impl Protocol {
     /// * `nickname` - 昵称
     /// * `sex` - 性别，`male` 或 `female` 或 `unknown`
     /// * `age` - 年龄 (number int32)
-    pub async fn get_stranger_info(&self, user_id: i64, no_cache: bool) -> Result<Response> {
+    pub async fn get_stranger_info(&self, user_id: i64, no_cache: bool) -> Result<StrangerInfo> {
         let data = json!({"user_id": user_id, "no_cache": no_cache});
-        self.send_request("get_stranger_info", data).await
+        self.call("get_stranger_info", data).await
     }
 
     /// 获取好友列表
@@ -458,8 +453,8 @@ impl Protocol {
     /// * `user_id` - QQ 号 (number int64)
     /// * `nickname` - 昵称
     /// * `remark` - 备注名
-    pub async fn get_friend_list(&self) -> Result<Response> {
-        self.send_request("get_friend_list", json!({})).await
+    pub async fn get_friend_list(&self) -> Result<Vec<FriendInfo>> {
+        self.call("get_friend_list", json!({})).await
     }
 
     /// 获取群信息
@@ -475,9 +470,9 @@ impl Protocol {
     /// * `group_name` - 群名称
     /// * `member_count` - 成员数 (number int32)
     /// * `max_member_count` - 最大成员数（群容量） (number int32)
-    pub async fn get_group_info(&self, group_id: i64, no_cache: bool) -> Result<Response> {
+    pub async fn get_group_info(&self, group_id: i64, no_cache: bool) -> Result<GroupInfo> {
         let data = json!({"group_id": group_id, "no_cache": no_cache});
-        self.send_request("get_group_info", data).await
+        self.call("get_group_info", data).await
     }
 
     /// 获取群列表
@@ -489,8 +484,8 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 响应内容为 JSON 数组，每个元素和 `get_group_info` 接口相同
-    pub async fn get_group_list(&self) -> Result<Response> {
-        self.send_request("get_group_list", json!({})).await
+    pub async fn get_group_list(&self) -> Result<Vec<GroupInfo>> {
+        self.call("get_group_list", json!({})).await
     }
 
     /// 获取群成员信息
@@ -523,9 +518,9 @@ impl Protocol {
         group_id: i64,
         user_id: i64,
         no_cache: bool,
-    ) -> Result<Response> {
+    ) -> Result<GroupMember> {
         let data = json!({"group_id": group_id, "user_id": user_id, "no_cache": no_cache});
-        self.send_request("get_group_member_info", data).await
+        self.call("get_group_member_info", data).await
     }
 
     /// 获取群成员列表
@@ -539,9 +534,9 @@ impl Protocol {
     /// 响应内容为 JSON 数组，每个元素的内容和 `get_group_member_info` 接口相同，
     /// 但对于同一个群组的同一个成员，获取列表时和获取单独的成员信息时，某些字段可能有所不同，
     /// 例如 `area`、`title` 等字段在获取列表时无法获得，具体应以单独的成员信息为准
-    pub async fn get_group_member_list(&self, group_id: i64) -> Result<Response> {
+    pub async fn get_group_member_list(&self, group_id: i64) -> Result<Vec<GroupMember>> {
         let data = json!({"group_id": group_id});
-        self.send_request("get_group_member_list", data).await
+        self.call("get_group_member_list", data).await
     }
 
     /// 获取群荣誉信息
@@ -550,8 +545,8 @@ impl Protocol {
     ///
     /// * `group_id` - 群号 (number int64)
     /// * `honor_type` - 要获取的群荣誉类型，可传入 `talkative` `performer` `legend`
-    ///                 `strong_newbie` `emotion` 以分别获取单个类型的群荣誉数据，
-    ///                 或传入 `all` 获取所有数据
+    ///   `strong_newbie` `emotion` 以分别获取单个类型的群荣誉数据，
+    ///   或传入 `all` 获取所有数据
     ///
     /// # 响应数据
     ///
@@ -562,9 +557,13 @@ impl Protocol {
     /// * `legend_list` - 群聊炽焰，仅 `type` 为 `legend` 或 `all` 时有数据
     /// * `strong_newbie_list` - 冒尖小春笋，仅 `type` 为 `strong_newbie` 或 `all` 时有数据
     /// * `emotion_list` - 快乐之源，仅 `type` 为 `emotion` 或 `all` 时有数据
-    pub async fn get_group_honor_info(&self, group_id: i64, honor_type: &str) -> Result<Response> {
+    pub async fn get_group_honor_info(
+        &self,
+        group_id: i64,
+        honor_type: &str,
+    ) -> Result<GroupHonorInfo> {
         let data = json!({"group_id": group_id, "type": honor_type});
-        self.send_request("get_group_honor_info", data).await
+        self.call("get_group_honor_info", data).await
     }
 
     // 凭证获取 API
@@ -578,9 +577,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// * `cookies` - Cookies
-    pub async fn get_cookies(&self, domain: &str) -> Result<Response> {
+    pub async fn get_cookies(&self, domain: &str) -> Result<Cookies> {
         let data = json!({"domain": domain});
-        self.send_request("get_cookies", data).await
+        self.call("get_cookies", data).await
     }
 
     /// 获取 CSRF Token
@@ -592,8 +591,8 @@ impl Protocol {
     /// # 响应数据
     ///
     /// * `token` - CSRF Token (number int32)
-    pub async fn get_csrf_token(&self) -> Result<Response> {
-        self.send_request("get_csrf_token", json!({})).await
+    pub async fn get_csrf_token(&self) -> Result<CsrfToken> {
+        self.call("get_csrf_token", json!({})).await
     }
 
     /// 获取 QQ 相关接口凭证
@@ -608,9 +607,9 @@ impl Protocol {
     ///
     /// * `cookies` - Cookies
     /// * `csrf_token` - CSRF Token (number int32)
-    pub async fn get_credentials(&self, domain: &str) -> Result<Response> {
+    pub async fn get_credentials(&self, domain: &str) -> Result<Credentials> {
         let data = json!({"domain": domain});
-        self.send_request("get_credentials", data).await
+        self.call("get_credentials", data).await
     }
 
     // 文件相关 API
@@ -629,9 +628,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// * `file` - 转换后的语音文件路径，如 `/home/somebody/cqhttp/data/record/0B38145AA44505000B38145AA4450500.mp3`
-    pub async fn get_record(&self, file: &str, out_format: &str) -> Result<Response> {
+    pub async fn get_record(&self, file: &str, out_format: &str) -> Result<FileInfo> {
         let data = json!({"file": file, "out_format": out_format});
-        self.send_request("get_record", data).await
+        self.call("get_record", data).await
     }
 
     /// 获取图片
@@ -643,9 +642,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// * `file` - 下载后的图片文件路径，如 `/home/somebody/cqhttp/data/image/6B4DE3DFD1BD271E3297859D41C530F5.jpg`
-    pub async fn get_image(&self, file: &str) -> Result<Response> {
+    pub async fn get_image(&self, file: &str) -> Result<FileInfo> {
         let data = json!({"file": file});
-        self.send_request("get_image", data).await
+        self.call("get_image", data).await
     }
 
     // 功能检查 API
@@ -659,8 +658,8 @@ impl Protocol {
     /// # 响应数据
     ///
     /// * `yes` - 是或否
-    pub async fn can_send_image(&self) -> Result<Response> {
-        self.send_request("can_send_image", json!({})).await
+    pub async fn can_send_image(&self) -> Result<CanSend> {
+        self.call("can_send_image", json!({})).await
     }
 
     /// 检查是否可以发送语音
@@ -672,8 +671,8 @@ impl Protocol {
     /// # 响应数据
     ///
     /// * `yes` - 是或否
-    pub async fn can_send_record(&self) -> Result<Response> {
-        self.send_request("can_send_record", json!({})).await
+    pub async fn can_send_record(&self) -> Result<CanSend> {
+        self.call("can_send_record", json!({})).await
     }
 
     // 状态和版本 API
@@ -694,8 +693,8 @@ impl Protocol {
     ///
     /// 通常情况下建议只使用 `online` 和 `good` 这两个字段来判断运行状态，
     /// 因为根据 OneBot 实现的不同，其它字段可能完全不同
-    pub async fn get_status(&self) -> Result<Response> {
-        self.send_request("get_status", json!({})).await
+    pub async fn get_status(&self) -> Result<Status> {
+        self.call("get_status", json!({})).await
     }
 
     /// 获取版本信息
@@ -710,8 +709,8 @@ impl Protocol {
     /// * `app_version` - 应用版本，如 `1.2.3`
     /// * `protocol_version` - OneBot 标准版本，如 `v11`
     /// * `……` - OneBot 实现自行添加的其它内容
-    pub async fn get_version_info(&self) -> Result<Response> {
-        self.send_request("get_version_info", json!({})).await
+    pub async fn get_version_info(&self) -> Result<VersionInfo> {
+        self.call("get_version_info", json!({})).await
     }
 
     // 系统操作 API
@@ -730,9 +729,9 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn set_restart(&self, delay: i32) -> Result<Response> {
+    pub async fn set_restart(&self, delay: i32) -> Result<()> {
         let data = json!({"delay": delay});
-        self.send_request("set_restart", data).await
+        self.call_unit("set_restart", data).await
     }
 
     /// 清理缓存
@@ -746,7 +745,7 @@ impl Protocol {
     /// # 响应数据
     ///
     /// 无
-    pub async fn clean_cache(&self) -> Result<Response> {
-        self.send_request("clean_cache", json!({})).await
+    pub async fn clean_cache(&self) -> Result<()> {
+        self.call_unit("clean_cache", json!({})).await
     }
 }
@@ -0,0 +1,216 @@
+// OneBot HTTP API + HTTP POST 上报
+// https://github.com/botuniverse/onebot-11/tree/master/communication
+
+use crate::{
+    application, config,
+    protocol::{
+        adapter::{self, ROUND_START_TIME, Request, Response},
+        error::ActionError,
+        record,
+    },
+};
+use anyhow::Result;
+use axum::{
+    Router,
+    body::Bytes,
+    http::{HeaderMap, StatusCode},
+};
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha1::Sha1;
+use std::time::{Duration, SystemTime};
+use tokio::{net::TcpListener, sync::mpsc};
+
+/// 动作请求转为 POST `/{action}`，HTTP 状态码非 2xx 时作为失败返回
+async fn call(client: &reqwest::Client, request: &Request) -> Result<Response, ActionError> {
+    let config = config::get();
+    let url = format!(
+        "{}/{}",
+        config.http_endpoint.trim_end_matches('/'),
+        request.action
+    );
+    let mut builder = client
+        .post(url)
+        .json(&request.params)
+        .timeout(Duration::from_secs(120));
+    if !config.token.is_empty() {
+        builder = builder.bearer_auth(&config.token);
+    }
+
+    let res = builder
+        .send()
+        .await
+        .map_err(|e| transport_error(&request.action, e))?;
+    let status = res.status();
+    let body = res
+        .bytes()
+        .await
+        .map_err(|e| transport_error(&request.action, e))?;
+    if !status.is_success() {
+        return Err(ActionError::Failed {
+            retcode: status.as_u16() as i32,
+            message: Some(String::from_utf8_lossy(&body).to_string()),
+        });
+    }
+    let mut raw: Value = serde_json::from_slice(&body)?;
+    // 补上 `echo` 以便回放时与请求配对
+    raw["echo"] = Value::from(request.echo.as_str());
+    record::inbound(&raw.to_string());
+    Ok(serde_json::from_value(raw)?)
+}
+
+/// 只有连接失败视为未连接，其余请求错误按超时、状态码、解码分别归类，以便调用方区分
+fn transport_error(action: &str, e: reqwest::Error) -> ActionError {
+    log::warn!("http request {} failed: {}", action, e);
+    if e.is_timeout() {
+        ActionError::Timeout
+    } else if e.is_connect() {
+        ActionError::NotConnected
+    } else if let Some(status) = e.status() {
+        ActionError::Failed {
+            retcode: status.as_u16() as i32,
+            message: status.canonical_reason().map(String::from),
+        }
+    } else if e.is_decode() || e.is_body() {
+        ActionError::Deserialize(serde_json::Error::io(std::io::Error::other(e)))
+    } else {
+        ActionError::Failed {
+            retcode: -1,
+            message: Some(e.to_string()),
+        }
+    }
+}
+
+async fn sender(mut receiver: mpsc::Receiver<Request>) {
+    let client = reqwest::Client::new();
+    while let Some(request) = receiver.recv().await {
+        let client = client.clone();
+        tokio::spawn(async move {
+            log::debug!("request: {:?}", request.action);
+            if let Ok(frame) = serde_json::to_string(&request) {
+                record::outbound(&frame);
+            }
+            let res = call(&client, &request).await;
+            _ = request.sender.send(res);
+        });
+    }
+}
+
+/// 校验 `X-Signature: sha1=<hmac>`，未配置 `secret` 时跳过
+fn verify(headers: &HeaderMap, body: &[u8]) -> bool {
+    let secret = config::get().secret.clone();
+    if secret.is_empty() {
+        return true;
+    }
+    let Some(signature) = headers
+        .get("X-Signature")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("sha1="))
+        .and_then(|v| hex::decode(v).ok())
+    else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+async fn receive(headers: HeaderMap, body: Bytes) -> StatusCode {
+    *ROUND_START_TIME.lock().await = SystemTime::now();
+    if !verify(&headers, &body) {
+        log::warn!("http post rejected: invalid signature");
+        return StatusCode::FORBIDDEN;
+    }
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(raw) => {
+            log::debug!("{}", raw);
+            record::inbound(&String::from_utf8_lossy(&body));
+            adapter::dispatch(raw);
+            StatusCode::NO_CONTENT
+        }
+        Err(e) => {
+            log::warn!("http post parse error: {}", e);
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+pub async fn serve() -> Result<()> {
+    let config = config::get();
+    let (req_tx, req_rx) = mpsc::channel(5);
+    let task_sender = tokio::spawn(sender(req_rx));
+    let bot = super::Protocol {
+        sender: Some(req_tx.clone()),
+    };
+    let self_id = match bot.get_login_info().await {
+        Ok(info) => info.user_id,
+        Err(e) => {
+            task_sender.abort();
+            return Err(e.into());
+        }
+    };
+    let listener = TcpListener::bind(&config.listen).await?;
+    log::info!("=> {}", config.http_endpoint);
+    log::info!("<= {}", config.listen);
+    super::update(self_id, req_tx.clone()).await;
+    application::connected(self_id).await;
+
+    let res = axum::serve(listener, Router::new().fallback(receive)).await;
+    if super::remove(self_id, &req_tx) {
+        application::disconnected(self_id).await;
+    }
+    task_sender.abort();
+    Ok(res?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::call;
+    use crate::protocol::{
+        adapter::Request,
+        error::ActionError,
+        mock::{MockServer, http_fixture_with},
+    };
+    use serde_json::json;
+    use tokio::sync::oneshot;
+
+    async fn request(endpoint: &str) -> Result<(), ActionError> {
+        let config = format!(
+            "token = \"\"\nowner = 1\nhttp_endpoint = \"{}\"\n",
+            endpoint
+        );
+        let _server = MockServer::start(10022, &config).await;
+        let (sender, _) = oneshot::channel();
+        let request = Request {
+            action: "get_status".to_string(),
+            params: json!({}),
+            echo: "1".to_string(),
+            created_at: std::time::Instant::now(),
+            sender,
+        };
+        call(&reqwest::Client::new(), &request).await.map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn errors_keep_their_cause() {
+        let endpoint = http_fixture_with("500 Internal Server Error", "oops".to_string()).await;
+        let res = request(&endpoint).await;
+        assert!(
+            matches!(res, Err(ActionError::Failed { retcode: 500, message: Some(ref m) }) if m == "oops")
+        );
+
+        let endpoint = http_fixture_with("200 OK", "not json".to_string()).await;
+        assert!(matches!(
+            request(&endpoint).await,
+            Err(ActionError::Deserialize(_))
+        ));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        assert!(matches!(
+            request(&endpoint).await,
+            Err(ActionError::NotConnected)
+        ));
+    }
+}
@@ -0,0 +1,355 @@
+// 消息段构造函数与链式构建消息，如 `Message::new().reply_to(id).at(qq).text("你好")`
+use super::{Media, Message, Segment};
+use std::{
+    ops::{Add, AddAssign},
+    path::PathBuf,
+};
+
+#[allow(unused)]
+impl Segment {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+
+    pub fn face(id: impl ToString) -> Self {
+        Self::Face { id: id.to_string() }
+    }
+
+    /// `file` 可以是 URL、本地路径或内存中的数据，见 [`Media`]
+    pub fn image(file: impl Into<Media>) -> Self {
+        Self::Image {
+            file: file.into(),
+            catagary: None,
+            url: None,
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }
+    }
+
+    /// 闪照
+    pub fn flash_image(file: impl Into<Media>) -> Self {
+        Self::Image {
+            file: file.into(),
+            catagary: Some("flash".to_string()),
+            url: None,
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }
+    }
+
+    pub fn record(file: impl Into<Media>) -> Self {
+        Self::Record {
+            file: file.into(),
+            magic: None,
+            url: None,
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }
+    }
+
+    pub fn video(file: impl Into<Media>) -> Self {
+        Self::Video {
+            file: file.into(),
+            url: None,
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }
+    }
+
+    pub fn at(qq: impl ToString) -> Self {
+        Self::At { qq: qq.to_string() }
+    }
+
+    pub fn at_all() -> Self {
+        Self::at("all")
+    }
+
+    pub fn rps() -> Self {
+        Self::Rps {
+            #[cfg(feature = "napcat")]
+            result: None,
+        }
+    }
+
+    pub fn dice() -> Self {
+        Self::Dice {
+            #[cfg(feature = "napcat")]
+            result: None,
+        }
+    }
+
+    pub fn shake() -> Self {
+        Self::Shake {}
+    }
+
+    pub fn poke(catagary: impl ToString, id: impl ToString) -> Self {
+        Self::Poke {
+            catagary: catagary.to_string(),
+            id: id.to_string(),
+            name: None,
+        }
+    }
+
+    pub fn anonymous() -> Self {
+        Self::Anonymous { ignore: None }
+    }
+
+    pub fn share(url: impl Into<String>, title: impl Into<String>) -> Self {
+        Self::Share {
+            url: url.into(),
+            title: title.into(),
+            content: None,
+            image: None,
+        }
+    }
+
+    /// 推荐好友
+    pub fn contact_user(user_id: i64) -> Self {
+        Self::Contact {
+            catagary: "qq".to_string(),
+            id: user_id.to_string(),
+        }
+    }
+
+    /// 推荐群
+    pub fn contact_group(group_id: i64) -> Self {
+        Self::Contact {
+            catagary: "group".to_string(),
+            id: group_id.to_string(),
+        }
+    }
+
+    pub fn location(lat: f64, lon: f64) -> Self {
+        Self::Location {
+            lat: lat.to_string(),
+            lon: lon.to_string(),
+            title: None,
+            content: None,
+        }
+    }
+
+    /// 平台音乐分享，`catagary` 为 qq、163 或 xm
+    pub fn music(catagary: impl ToString, id: impl ToString) -> Self {
+        Self::Music {
+            catagary: catagary.to_string(),
+            id: Some(id.to_string()),
+            url: None,
+            audio: None,
+            title: None,
+            content: None,
+            image: None,
+        }
+    }
+
+    /// 自定义音乐分享
+    pub fn custom_music(
+        url: impl Into<String>,
+        audio: impl Into<String>,
+        title: impl Into<String>,
+    ) -> Self {
+        Self::Music {
+            catagary: "custom".to_string(),
+            id: None,
+            url: Some(url.into()),
+            audio: Some(audio.into()),
+            title: Some(title.into()),
+            content: None,
+            image: None,
+        }
+    }
+
+    pub fn reply(message_id: impl ToString) -> Self {
+        Self::Reply {
+            id: message_id.to_string(),
+        }
+    }
+
+    pub fn forward(id: impl ToString) -> Self {
+        Self::Forward { id: id.to_string() }
+    }
+
+    /// 引用已有消息的转发节点
+    pub fn node(message_id: impl ToString) -> Self {
+        Self::Node {
+            id: Some(message_id.to_string()),
+            user_id: None,
+            nickname: None,
+            content: None,
+        }
+    }
+
+    /// 自定义发送者与内容的转发节点
+    pub fn custom_node(
+        user_id: impl ToString,
+        nickname: impl Into<String>,
+        content: impl Into<Message>,
+    ) -> Self {
+        Self::Node {
+            id: None,
+            user_id: Some(user_id.to_string()),
+            nickname: Some(nickname.into()),
+            content: Some(content.into()),
+        }
+    }
+
+    pub fn xml(data: impl Into<String>) -> Self {
+        Self::Xml { data: data.into() }
+    }
+
+    pub fn json(data: impl Into<String>) -> Self {
+        Self::Json { data: data.into() }
+    }
+
+    /// 上传文件，`name` 为显示的文件名
+    #[cfg(feature = "napcat")]
+    pub fn file(file: impl Into<Media>, name: impl Into<String>) -> Self {
+        Self::File {
+            file: file.into(),
+            name: Some(name.into()),
+            file_id: None,
+            file_size: None,
+            url: None,
+        }
+    }
+
+    #[cfg(feature = "napcat")]
+    pub fn mface(
+        emoji_package_id: impl ToString,
+        emoji_id: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Self {
+        Self::Mface {
+            emoji_package_id: Some(emoji_package_id.to_string()),
+            emoji_id: emoji_id.into(),
+            key: key.into(),
+            summary: None,
+            url: None,
+        }
+    }
+
+    #[cfg(feature = "napcat")]
+    pub fn markdown(content: impl Into<String>) -> Self {
+        Self::Markdown {
+            content: content.into(),
+        }
+    }
+
+    #[cfg(feature = "napcat")]
+    pub fn miniapp(data: impl Into<String>) -> Self {
+        Self::Miniapp { data: data.into() }
+    }
+}
+
+#[allow(unused)]
+impl Message {
+    /// 追加一个消息段
+    pub fn segment(mut self, segment: Segment) -> Self {
+        self.push(segment);
+        self
+    }
+
+    pub fn text(self, text: impl Into<String>) -> Self {
+        self.segment(Segment::text(text))
+    }
+
+    pub fn at(self, qq: impl ToString) -> Self {
+        self.segment(Segment::at(qq))
+    }
+
+    pub fn at_all(self) -> Self {
+        self.segment(Segment::at_all())
+    }
+
+    pub fn face(self, id: impl ToString) -> Self {
+        self.segment(Segment::face(id))
+    }
+
+    pub fn image_url(self, url: impl Into<String>) -> Self {
+        self.segment(Segment::image(Media::Url(url.into())))
+    }
+
+    /// 发送内存中的图片，如生成的图表，无需写入临时文件
+    pub fn image_bytes(self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.segment(Segment::image(Media::Bytes(bytes.into())))
+    }
+
+    /// 发送本地图片，路径需要 OneBot 实现也能访问
+    pub fn image_path(self, path: impl Into<PathBuf>) -> Self {
+        self.segment(Segment::image(Media::Path(path.into())))
+    }
+
+    pub fn record(self, file: impl Into<Media>) -> Self {
+        self.segment(Segment::record(file))
+    }
+
+    pub fn video(self, file: impl Into<Media>) -> Self {
+        self.segment(Segment::video(file))
+    }
+
+    /// 引用回复，回复段总是放在最前
+    pub fn reply_to(mut self, message_id: impl ToString) -> Self {
+        self.insert(0, Segment::reply(message_id));
+        self
+    }
+}
+
+impl<T: Into<Message>> Add<T> for Message {
+    type Output = Message;
+
+    fn add(mut self, rhs: T) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl<T: Into<Message>> AddAssign<T> for Message {
+    fn add_assign(&mut self, rhs: T) {
+        self.0.extend(rhs.into());
+    }
+}
+
+impl Extend<Segment> for Message {
+    fn extend<I: IntoIterator<Item = Segment>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl FromIterator<Segment> for Message {
+    fn from_iter<I: IntoIterator<Item = Segment>>(iter: I) -> Self {
+        Message(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::message::{Message, Segment};
+
+    #[test]
+    fn builds_in_order() {
+        let message = Message::new()
+            .at(42)
+            .text(" 看图")
+            .image_bytes(b"png")
+            .reply_to(7);
+        assert_eq!(
+            message.to_cq_string(),
+            "[CQ:reply,id=7][CQ:at,qq=42] 看图[CQ:image,file=base64://cG5n]"
+        );
+    }
+
+    #[test]
+    fn concatenates() {
+        let mut message = Message::from("a") + Segment::face(1) + "b";
+        message += Message::new().at_all();
+        message.extend([Segment::dice()]);
+        let collected: Message = message.into_iter().collect();
+        assert_eq!(
+            collected.to_cq_string(),
+            "a[CQ:face,id=1]b[CQ:at,qq=all][CQ:dice]"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+// CQ 码格式，如 `[CQ:at,qq=123]你好`，事件的 `raw_message` 与字符串上报格式使用
+//
+// 纯文本中转义 `&`、`[`、`]`，参数值中另外转义 `,`
+use super::{Message, Segment};
+use serde::de::Error;
+use serde_json::{Map, Value};
+
+/// 值为布尔的参数，CQ 码中写作 `1`/`0`
+const BOOL_PARAMS: &[&str] = &["cache", "proxy", "magic", "ignore"];
+/// 值为数字的参数
+const NUMBER_PARAMS: &[&str] = &["timeout"];
+
+impl Message {
+    /// 解析 CQ 码字符串
+    pub fn from_cq_str(s: &str) -> serde_json::Result<Self> {
+        let mut message = Message::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("[CQ:") {
+            if start > 0 {
+                message.push(text(&rest[..start]));
+            }
+            let body = &rest[start + 4..];
+            let end = body
+                .find(']')
+                .ok_or_else(|| serde_json::Error::custom(format!("unclosed CQ code: {}", rest)))?;
+            message.push(parse_code(&body[..end])?);
+            rest = &body[end + 1..];
+        }
+        if !rest.is_empty() {
+            message.push(text(rest));
+        }
+        Ok(message)
+    }
+
+    /// 转换为 CQ 码字符串
+    pub fn to_cq_string(&self) -> String {
+        self.0.iter().map(Segment::to_cq_string).collect()
+    }
+}
+
+impl Segment {
+    /// 转换为 CQ 码，参数按名称排序，纯文本段只做转义
+    pub fn to_cq_string(&self) -> String {
+        if let Segment::Text { text } = self {
+            return escape(text, false);
+        }
+        let value = serde_json::to_value(self).expect("segment is always serializable");
+        let mut code = format!("[CQ:{}", value["type"].as_str().unwrap_or_default());
+        if let Some(data) = value["data"].as_object() {
+            for (key, value) in data {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Bool(b) => if *b { "1" } else { "0" }.to_string(),
+                    Value::Array(_) => serde_json::from_value::<Message>(value.clone())
+                        .map(|m| m.to_cq_string())
+                        .unwrap_or_default(),
+                    Value::Null => continue,
+                    other => other.to_string(),
+                };
+                code.push_str(&format!(",{}={}", key, escape(&value, true)));
+            }
+        }
+        code.push(']');
+        code
+    }
+}
+
+fn text(s: &str) -> Segment {
+    Segment::Text { text: unescape(s) }
+}
+
+/// 解析 `[CQ:` 与 `]` 之间的部分，如 `image,file=a.jpg`
+fn parse_code(code: &str) -> serde_json::Result<Segment> {
+    let mut parts = code.split(',');
+    let kind = parts.next().unwrap_or_default();
+    let mut data = Map::new();
+    for param in parts {
+        let (key, value) = param
+            .split_once('=')
+            .ok_or_else(|| serde_json::Error::custom(format!("invalid CQ param: {}", param)))?;
+        let value = unescape(value);
+        let value = if BOOL_PARAMS.contains(&key) {
+            Value::Bool(matches!(value.as_str(), "1" | "true" | "yes"))
+        } else if NUMBER_PARAMS.contains(&key)
+            && let Ok(n) = value.parse::<u64>()
+        {
+            n.into()
+        } else {
+            Value::String(value)
+        };
+        data.insert(key.to_string(), value);
+    }
+    let mut segment = Map::new();
+    segment.insert("type".to_string(), kind.into());
+    segment.insert("data".to_string(), data.into());
+    serde_json::from_value(segment.into())
+}
+
+fn escape(s: &str, param: bool) -> String {
+    let s = s
+        .replace('&', "&amp;")
+        .replace('[', "&#91;")
+        .replace(']', "&#93;");
+    if param { s.replace(',', "&#44;") } else { s }
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&#44;", ",")
+        .replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::{
+        event::Event,
+        message::{Message, Segment},
+        mock::group_message,
+    };
+
+    #[test]
+    fn round_trips_with_escaping() {
+        let raw = "[CQ:reply,id=7]&#91;公告&#93; a&amp;b,c[CQ:at,qq=all] \
+                   [CQ:image,cache=0,file=https://x/?a=1&amp;b=2&#44;3]";
+        let message = Message::from_cq_str(raw).unwrap();
+        assert_eq!(message.len(), 5);
+        assert!(matches!(&message[1], Segment::Text { text } if text == "[公告] a&b,c"));
+        assert!(matches!(&message[2], Segment::At { qq } if qq == "all"));
+        assert!(matches!(
+            &message[4],
+            Segment::Image { file, cache: Some(false), .. } if file.to_string() == "https://x/?a=1&b=2,3"
+        ));
+        assert_eq!(message.to_cq_string(), raw);
+    }
+
+    #[test]
+    fn nested_and_unit_segments() {
+        let node: Message = Segment::Node {
+            id: None,
+            user_id: Some("1".to_string()),
+            nickname: Some("a,b".to_string()),
+            content: Some(Message::from_cq_str("hi[CQ:face,id=1]").unwrap()),
+        }
+        .into();
+        let raw = node.to_cq_string();
+        assert_eq!(
+            raw,
+            "[CQ:node,content=hi&#91;CQ:face&#44;id=1&#93;,nickname=a&#44;b,user_id=1]"
+        );
+        let parsed = Message::from_cq_str(&raw).unwrap();
+        assert_eq!(parsed.to_cq_string(), raw);
+        assert!(matches!(
+            Message::from_cq_str("[CQ:dice]").unwrap()[0],
+            Segment::Dice { .. }
+        ));
+        assert!(Message::from_cq_str("[CQ:at,qq=1").is_err());
+        let unknown = Message::from_cq_str("[CQ:keyboard,id=1&#44;2]").unwrap();
+        assert!(
+            matches!(&unknown[0], Segment::Unknown { kind, data } if kind == "keyboard" && data["id"] == "1,2")
+        );
+        assert_eq!(unknown.to_cq_string(), "[CQ:keyboard,id=1&#44;2]");
+    }
+
+    #[test]
+    fn string_format_events() {
+        let mut raw = group_message(1, 2, "");
+        raw["message"] = "[CQ:at,qq=3] 早".into();
+        raw["time"] = 0.into();
+        raw["self_id"] = 10.into();
+        raw["message_id"] = 0.into();
+        let Event::MessageEvent(event) = serde_json::from_value(raw).unwrap() else {
+            unreachable!()
+        };
+        assert!(matches!(&event.message()[0], Segment::At { qq } if qq == "3"));
+        assert_eq!(event.message().plain_text(), " 早");
+    }
+}
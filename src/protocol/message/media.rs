@@ -0,0 +1,192 @@
+// 图片、语音、视频消息段的文件来源
+//
+// 内存中的数据以 `base64://` 发送，本地路径以 `file://` 发送，由 OneBot 实现读取
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// 单条消息以 `base64://` 发送的数据总量上限，过大的帧可能被实现或中间代理拒绝
+pub const MAX_MEDIA_SIZE: usize = 10 * 1024 * 1024;
+
+/// 消息中的媒体数据过大，本地校验未通过，请求没有发出
+#[derive(Debug)]
+pub struct MediaTooLarge {
+    pub size: usize,
+    pub limit: usize,
+}
+
+impl fmt::Display for MediaTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "media too large ({} > {} bytes)", self.size, self.limit)
+    }
+}
+
+impl std::error::Error for MediaTooLarge {}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Media {
+    /// URL，或实现能识别的其他形式，如收到的文件名、`base64://`
+    Url(String),
+    /// 内存中的数据，发送时编码为 `base64://`
+    Bytes(Vec<u8>),
+    /// OneBot 实现所在机器上的路径，发送时转换为 `file://`
+    Path(PathBuf),
+}
+
+impl Media {
+    /// 发送时以 `base64://` 传输的数据大小，超过 [`MAX_MEDIA_SIZE`] 时不应发送
+    pub fn inline_size(&self) -> usize {
+        match self {
+            Media::Bytes(bytes) => bytes.len(),
+            Media::Url(url) => url
+                .strip_prefix("base64://")
+                .map_or(0, |data| data.len() / 4 * 3),
+            Media::Path(_) => 0,
+        }
+    }
+}
+
+/// 输出 OneBot 能识别的形式
+impl fmt::Display for Media {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Media::Url(url) => write!(f, "{}", url),
+            Media::Bytes(bytes) => {
+                write!(f, "base64://{}", general_purpose::STANDARD.encode(bytes))
+            }
+            Media::Path(path) => write!(f, "{}", file_uri(path)),
+        }
+    }
+}
+
+/// 日志中不展开数据
+impl fmt::Debug for Media {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Media::Url(url) if url.len() > 20 => {
+                write!(f, "{}...", url.chars().take(20).collect::<String>())
+            }
+            Media::Url(url) => write!(f, "{}", url),
+            Media::Bytes(bytes) => write!(f, "<{} bytes>", bytes.len()),
+            Media::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl Serialize for Media {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Media {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Media::Url)
+    }
+}
+
+impl From<String> for Media {
+    fn from(url: String) -> Self {
+        Media::Url(url)
+    }
+}
+
+impl From<&str> for Media {
+    fn from(url: &str) -> Self {
+        Media::Url(url.to_string())
+    }
+}
+
+impl From<&String> for Media {
+    fn from(url: &String) -> Self {
+        Media::Url(url.clone())
+    }
+}
+
+impl From<Vec<u8>> for Media {
+    fn from(bytes: Vec<u8>) -> Self {
+        Media::Bytes(bytes)
+    }
+}
+
+impl From<PathBuf> for Media {
+    fn from(path: PathBuf) -> Self {
+        Media::Path(path)
+    }
+}
+
+impl From<&Path> for Media {
+    fn from(path: &Path) -> Self {
+        Media::Path(path.to_path_buf())
+    }
+}
+
+fn file_uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_MEDIA_SIZE, Media};
+    use crate::protocol::message::{Message, Segment};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    #[test]
+    fn serializes_to_onebot_forms() {
+        let message = Message::new()
+            .image_bytes(b"png".to_vec())
+            .image_path("/tmp/a.png")
+            .image_url("https://x/a.png");
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            json!([
+                { "type": "image", "data": { "file": "base64://cG5n" } },
+                { "type": "image", "data": { "file": "file:///tmp/a.png" } },
+                { "type": "image", "data": { "file": "https://x/a.png" } },
+            ])
+        );
+        assert_eq!(format!("{:?}", message[0]), "[image:<3 bytes>]");
+        let received: Segment =
+            serde_json::from_value(json!({ "type": "record", "data": { "file": "a.amr" } }))
+                .unwrap();
+        assert!(matches!(received, Segment::Record { file: Media::Url(f), .. } if f == "a.amr"));
+        assert!(matches!(Media::from(PathBuf::from("b")), Media::Path(_)));
+    }
+
+    #[test]
+    fn oversized_media_is_rejected() {
+        let small = Message::new().image_bytes(vec![0; 16]);
+        assert!(small.check_media_size().is_ok());
+        let large = Message::from("图表").image_bytes(vec![0; MAX_MEDIA_SIZE + 1]);
+        assert!(large.check_media_size().is_err());
+        let many = (0..3).fold(Message::new(), |m, _| {
+            m.image_bytes(vec![0; MAX_MEDIA_SIZE / 2])
+        });
+        assert!(many.check_media_size().is_err());
+        let forward: Message = Segment::custom_node(1, "a", many).into();
+        assert!(forward.check_media_size().is_err());
+        let encoded = format!("base64://{}", "A".repeat(MAX_MEDIA_SIZE / 3 * 4 + 8));
+        assert!(
+            Message::new()
+                .image_url(encoded)
+                .check_media_size()
+                .is_err()
+        );
+    }
+}
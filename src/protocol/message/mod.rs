@@ -1,9 +1,22 @@
-use serde::{Deserialize, Serialize};
+use crate::protocol::tagged;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser::SerializeMap};
+use serde_json::Value;
 use std::fmt::{self, Debug};
 
+mod builder;
+mod cq;
+mod media;
+
+pub use media::{MAX_MEDIA_SIZE, Media, MediaTooLarge};
+
 /// OneBot 消息段枚举，支持所有标准消息段类型
-#[derive(Deserialize, Serialize)]
-#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(
+    remote = "Self",
+    tag = "type",
+    content = "data",
+    rename_all = "snake_case"
+)]
 pub enum Segment {
     /// 纯文本消息段
     Text {
@@ -17,8 +30,8 @@ pub enum Segment {
     },
     /// 图片消息段
     Image {
-        /// 图片文件名、URL、绝对路径或 Base64 编码
-        file: String,
+        /// 图片文件名、URL、本地路径或内存中的数据
+        file: Media,
         /// 图片类型，flash 表示闪照，无此参数表示普通图片
         #[serde(skip_serializing_if = "Option::is_none")]
         #[serde(rename = "type")]
@@ -38,8 +51,8 @@ pub enum Segment {
     },
     /// 语音消息段
     Record {
-        /// 语音文件名、URL、绝对路径或 Base64 编码
-        file: String,
+        /// 语音文件名、URL、本地路径或内存中的数据
+        file: Media,
         /// 是否变声，默认 false
         #[serde(skip_serializing_if = "Option::is_none")]
         magic: Option<bool>,
@@ -58,8 +71,8 @@ pub enum Segment {
     },
     /// 短视频消息段
     Video {
-        /// 视频文件名、URL、绝对路径或 Base64 编码
-        file: String,
+        /// 视频文件名、URL、本地路径或内存中的数据
+        file: Media,
         /// 视频 URL（接收时）
         #[serde(skip_serializing_if = "Option::is_none")]
         url: Option<String>,
@@ -79,11 +92,23 @@ pub enum Segment {
         qq: String,
     },
     /// 猜拳魔法表情
-    Rps,
+    Rps {
+        /// 猜拳结果（接收时）
+        #[cfg(feature = "napcat")]
+        #[serde(default, deserialize_with = "lenient_string")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<String>,
+    },
     /// 掷骰子魔法表情
-    Dice,
+    Dice {
+        /// 点数（接收时）
+        #[cfg(feature = "napcat")]
+        #[serde(default, deserialize_with = "lenient_string")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<String>,
+    },
     /// 窗口抖动（戳一戳的简化形式）
-    Shake,
+    Shake {},
     /// 戳一戳消息段
     Poke {
         /// 戳一戳类型
@@ -194,30 +219,157 @@ pub enum Segment {
         /// JSON 内容
         data: String,
     },
+    /// 文件消息段，群文件或私聊文件
+    #[cfg(feature = "napcat")]
+    File {
+        /// 文件 URL、本地路径或内存中的数据，接收时为文件名
+        file: Media,
+        /// 发送时显示的文件名
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// 文件 ID（接收时）
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file_id: Option<String>,
+        /// 文件大小（接收时）
+        #[serde(default, deserialize_with = "lenient_string")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file_size: Option<String>,
+        /// 文件 URL（接收时，部分情况下提供）
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    /// 商城表情
+    #[cfg(feature = "napcat")]
+    Mface {
+        /// 表情包 ID
+        #[serde(default, deserialize_with = "lenient_string")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        emoji_package_id: Option<String>,
+        /// 表情 ID
+        emoji_id: String,
+        /// 表情 key
+        key: String,
+        /// 表情描述，如 `[开心]`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+        /// 表情图片 URL（接收时）
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    /// Markdown 消息段
+    #[cfg(feature = "napcat")]
+    Markdown {
+        /// Markdown 内容
+        content: String,
+    },
+    /// 小程序卡片，部分实现称为 lightapp
+    #[cfg(feature = "napcat")]
+    #[serde(alias = "lightapp")]
+    Miniapp {
+        /// 卡片 JSON
+        data: String,
+    },
+    /// 未识别的消息段，保留原始数据，发送时原样输出
+    #[serde(skip)]
+    Unknown {
+        /// 消息段类型
+        kind: String,
+        data: Value,
+    },
 }
 
 /// OneBot 消息，由多个消息段组成的数组
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Message(pub Vec<Segment>);
 
+/// 同时接受数组格式与 CQ 码字符串格式（`message_format: string`）
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Value::deserialize(deserializer)? {
+            Value::String(s) => Message::from_cq_str(&s).map_err(de::Error::custom),
+            value => Vec::<Segment>::deserialize(value)
+                .map(Message)
+                .map_err(de::Error::custom),
+        }
+    }
+}
+
+/// `Segment` 已定义的 `type`
+const KNOWN_SEGMENTS: &[&str] = &[
+    "text",
+    "face",
+    "image",
+    "record",
+    "video",
+    "at",
+    "rps",
+    "dice",
+    "shake",
+    "poke",
+    "anonymous",
+    "share",
+    "contact",
+    "location",
+    "music",
+    "reply",
+    "forward",
+    "node",
+    "xml",
+    "json",
+    #[cfg(feature = "napcat")]
+    "file",
+    #[cfg(feature = "napcat")]
+    "mface",
+    #[cfg(feature = "napcat")]
+    "markdown",
+    #[cfg(feature = "napcat")]
+    "miniapp",
+    #[cfg(feature = "napcat")]
+    "lightapp",
+];
+
+impl<'de> Deserialize<'de> for Segment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        tagged::deserialize(
+            deserializer,
+            "type",
+            KNOWN_SEGMENTS,
+            Segment::deserialize,
+            |kind, mut data| {
+                Ok(Segment::Unknown {
+                    kind,
+                    data: data["data"].take(),
+                })
+            },
+        )
+    }
+}
+
+impl Serialize for Segment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Segment::Unknown { kind, data } = self else {
+            return Segment::serialize(self, serializer);
+        };
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", kind)?;
+        if !data.is_null() {
+            map.serialize_entry("data", data)?;
+        }
+        map.end()
+    }
+}
+
 impl Debug for Segment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Segment::Text { text } => write!(f, "{}", text),
             Segment::Face { id } => write!(f, "[face:{}]", id),
-            Segment::Image { file, .. } => {
-                if file.len() < 20 {
-                    write!(f, "[image:{}]", file)
-                } else {
-                    write!(
-                        f,
-                        "[image:{}...]",
-                        file.chars().take(20).collect::<String>()
-                    )
-                }
-            }
-            Segment::Record { file, .. } => write!(f, "[record:{}]", file),
-            Segment::Video { file, .. } => write!(f, "[video:{}]", file),
+            Segment::Image { file, .. } => write!(f, "[image:{:?}]", file),
+            Segment::Record { file, .. } => write!(f, "[record:{:?}]", file),
+            Segment::Video { file, .. } => write!(f, "[video:{:?}]", file),
             Segment::At { qq } => {
                 if qq == "all" {
                     write!(f, "[@全体成员]")
@@ -225,9 +377,17 @@ impl Debug for Segment {
                     write!(f, "[@{}]", qq)
                 }
             }
-            Segment::Rps => write!(f, "[猜拳]"),
-            Segment::Dice => write!(f, "[掷骰子]"),
-            Segment::Shake => write!(f, "[戳一戳]"),
+            #[cfg(feature = "napcat")]
+            Segment::Rps {
+                result: Some(result),
+            } => write!(f, "[猜拳:{}]", result),
+            Segment::Rps { .. } => write!(f, "[猜拳]"),
+            #[cfg(feature = "napcat")]
+            Segment::Dice {
+                result: Some(result),
+            } => write!(f, "[掷骰子:{}]", result),
+            Segment::Dice { .. } => write!(f, "[掷骰子]"),
+            Segment::Shake {} => write!(f, "[戳一戳]"),
             Segment::Poke { name, .. } => {
                 if let Some(name) = name {
                     write!(f, "[{}]", name)
@@ -273,6 +433,21 @@ impl Debug for Segment {
             }
             Segment::Xml { .. } => write!(f, "[XML消息]"),
             Segment::Json { .. } => write!(f, "[JSON消息]"),
+            #[cfg(feature = "napcat")]
+            Segment::File { file, name, .. } => match name {
+                Some(name) => write!(f, "[文件:{}]", name),
+                None => write!(f, "[文件:{:?}]", file),
+            },
+            #[cfg(feature = "napcat")]
+            Segment::Mface { summary, .. } => match summary {
+                Some(summary) => write!(f, "[商城表情:{}]", summary),
+                None => write!(f, "[商城表情]"),
+            },
+            #[cfg(feature = "napcat")]
+            Segment::Markdown { .. } => write!(f, "[Markdown消息]"),
+            #[cfg(feature = "napcat")]
+            Segment::Miniapp { .. } => write!(f, "[小程序]"),
+            Segment::Unknown { kind, .. } => write!(f, "[{}]", kind),
         }
     }
 }
@@ -285,19 +460,6 @@ impl fmt::Display for Message {
     }
 }
 
-impl Segment {
-    pub fn image(url: String) -> Self {
-        Self::Image {
-            file: url,
-            catagary: None,
-            url: None,
-            cache: None,
-            proxy: None,
-            timeout: None,
-        }
-    }
-}
-
 impl Message {
     /// 创建一个空消息
     pub fn new() -> Self {
@@ -334,6 +496,37 @@ impl Message {
         self.0.len()
     }
 
+    /// 以 `base64://` 发送的媒体（包括转发节点内的）总大小
+    pub fn inline_size(&self) -> usize {
+        self.0
+            .iter()
+            .map(|segment| match segment {
+                Segment::Image { file, .. }
+                | Segment::Record { file, .. }
+                | Segment::Video { file, .. } => file.inline_size(),
+                #[cfg(feature = "napcat")]
+                Segment::File { file, .. } => file.inline_size(),
+                Segment::Node {
+                    content: Some(content),
+                    ..
+                } => content.inline_size(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// 检查整条消息以 `base64://` 发送的媒体总大小是否超过 [`MAX_MEDIA_SIZE`]
+    pub fn check_media_size(&self) -> Result<(), MediaTooLarge> {
+        let size = self.inline_size();
+        if size > MAX_MEDIA_SIZE {
+            return Err(MediaTooLarge {
+                size,
+                limit: MAX_MEDIA_SIZE,
+            });
+        }
+        Ok(())
+    }
+
     /// 提取所有纯文本内容并合并
     pub fn plain_text(&self) -> String {
         self.0
@@ -347,6 +540,19 @@ impl Message {
     }
 }
 
+/// NapCat 的部分字段有时为数字，有时为字符串，统一为字符串
+#[cfg(feature = "napcat")]
+fn lenient_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s),
+        Some(value) => Some(value.to_string()),
+    })
+}
+
 impl Default for Message {
     fn default() -> Self {
         Self::new()
@@ -419,3 +625,52 @@ impl From<String> for Message {
         Segment::Text { text }.into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Message, Segment};
+    use serde_json::json;
+
+    #[test]
+    fn unit_segments_accept_empty_data() {
+        let message: Message = serde_json::from_value(json!([
+            { "type": "shake", "data": {} },
+            { "type": "dice", "data": {} },
+            { "type": "anonymous", "data": {} },
+        ]))
+        .unwrap();
+        assert_eq!(format!("{}", message), "[戳一戳] [掷骰子] [匿名] ");
+        assert_eq!(
+            serde_json::to_value(Segment::rps()).unwrap(),
+            json!({ "type": "rps", "data": {} })
+        );
+    }
+
+    #[cfg(feature = "napcat")]
+    #[test]
+    fn napcat_segments() {
+        let message: Message = serde_json::from_value(json!([
+            { "type": "dice", "data": { "result": 3 } },
+            { "type": "rps", "data": { "result": "1" } },
+            { "type": "file", "data": { "file": "a.zip", "file_id": "x", "file_size": 1024 } },
+            { "type": "mface", "data": {
+                "emoji_package_id": 233, "emoji_id": "e", "key": "k", "summary": "[开心]",
+            } },
+            { "type": "markdown", "data": { "content": "# hi" } },
+            { "type": "lightapp", "data": { "data": "{}" } },
+        ]))
+        .unwrap();
+        assert_eq!(
+            format!("{}", message),
+            "[掷骰子:3] [猜拳:1] [文件:a.zip] [商城表情:[开心]] [Markdown消息] [小程序] "
+        );
+        assert!(matches!(
+            &message[2],
+            Segment::File { file_size: Some(size), .. } if size == "1024"
+        ));
+        assert_eq!(
+            Message::from(Segment::file(vec![1, 2], "a.bin")).to_cq_string(),
+            "[CQ:file,file=base64://AQI=,name=a.bin]"
+        );
+    }
+}
@@ -0,0 +1,372 @@
+// 测试用的 OneBot 实现，在本地端口模拟 NapCat 的正向 WebSocket
+//
+// bot 经由真实的 `connect` 与 `event_loop` 连入，测试通过 `push` / `replay` 上报事件，
+// 用 `respond` / `respond_once` 预设动作的完整响应，用 `wait_action` 断言 bot 发出的动作；
+// 未预设时 `send_*_msg` 返回新的 `message_id`，其余动作返回空数据
+use crate::{
+    application, config,
+    protocol::{self, RequestSender, adapter, bots, transport::WsTransport},
+};
+use dashmap::DashMap;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI32, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::{self, MutexGuard, Notify, mpsc},
+    task::JoinHandle,
+};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 配置与应用均为全局状态，同一时间只运行一个模拟服务器
+static LOCK: sync::Mutex<()> = sync::Mutex::const_new(());
+
+/// 分配给上报消息的 `message_id`，跨测试递增以免被去重
+static MESSAGE_ID: AtomicI32 = AtomicI32::new(1);
+
+/// bot 发出的一次动作调用
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub action: String,
+    pub params: Value,
+}
+
+#[derive(Default)]
+struct Responses {
+    /// 按动作名预设的响应，每次调用都使用
+    fixed: DashMap<String, Value>,
+    /// 按动作名排队的一次性响应，优先于 `fixed`，`None` 表示不应答
+    queued: DashMap<String, VecDeque<Option<Value>>>,
+}
+
+impl Responses {
+    /// 下一次调用 `action` 的响应，不含 `echo`
+    fn next(&self, action: &str) -> Option<Value> {
+        if let Some(reply) = self.queued.get_mut(action).and_then(|mut q| q.pop_front()) {
+            return reply;
+        }
+        if let Some(response) = self.fixed.get(action) {
+            return Some(response.clone());
+        }
+        if action.starts_with("send_") && action.ends_with("_msg") {
+            let message_id = MESSAGE_ID.fetch_add(1, Ordering::Relaxed);
+            return Some(ok(json!({ "message_id": message_id })));
+        }
+        Some(ok(Value::Null))
+    }
+}
+
+/// 成功的响应
+pub fn ok(data: Value) -> Value {
+    json!({ "status": "ok", "retcode": 0, "data": data })
+}
+
+/// 失败的响应
+pub fn failed(retcode: i32, message: &str) -> Value {
+    json!({ "status": "failed", "retcode": retcode, "data": null, "message": message })
+}
+
+#[derive(Default)]
+struct Recorder {
+    actions: Mutex<Vec<Action>>,
+    notify: Notify,
+}
+
+pub struct MockServer {
+    pub self_id: i64,
+    events: mpsc::UnboundedSender<String>,
+    responses: Arc<Responses>,
+    recorder: Arc<Recorder>,
+    /// bot 注册时使用的请求通道，退出时据此注销
+    sender: RequestSender,
+    tasks: Vec<JoinHandle<()>>,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl MockServer {
+    /// 以 `config` 为配置启动，返回时应用已加载且 bot 已注册
+    pub async fn start(self_id: i64, config: &str) -> Self {
+        let guard = LOCK.lock().await;
+        config::load_str(config).expect("invalid test config");
+        application::startup().await;
+        application::config_reloaded().await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("ws://{}/", listener.local_addr().unwrap());
+        let (events, events_rx) = mpsc::unbounded_channel();
+        let responses = Arc::new(Responses::default());
+        let recorder = Arc::new(Recorder::default());
+
+        let server = tokio::spawn(serve(
+            listener,
+            self_id,
+            events_rx,
+            responses.clone(),
+            recorder.clone(),
+        ));
+        let bot = tokio::spawn(async move {
+            let (ws, self_id) = adapter::connect(&endpoint, "").await.unwrap();
+            if let Err(e) = adapter::event_loop(WsTransport::new(ws), self_id).await {
+                log::warn!("mock bot: {}", e);
+            }
+        });
+
+        tokio::time::timeout(TIMEOUT, async {
+            while !bots().contains(&self_id) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("bot did not connect");
+        let sender = protocol::get_bot_by_id(self_id)
+            .sender
+            .expect("registered bot has a sender");
+
+        Self {
+            self_id,
+            events,
+            responses,
+            recorder,
+            sender,
+            tasks: vec![server, bot],
+            _guard: guard,
+        }
+    }
+
+    /// 预设 `action` 每次调用的完整响应，如 `ok(data)`、`failed(retcode, message)`
+    pub fn respond(&self, action: &str, response: Value) {
+        self.responses.fixed.insert(action.to_string(), response);
+    }
+
+    /// 为 `action` 的下一次调用排队一个响应，多次调用时按顺序使用
+    pub fn respond_once(&self, action: &str, response: Value) {
+        self.responses
+            .queued
+            .entry(action.to_string())
+            .or_default()
+            .push_back(Some(response));
+    }
+
+    /// `action` 的下一次调用不应答，用于测试超时
+    pub fn ignore_once(&self, action: &str) {
+        self.responses
+            .queued
+            .entry(action.to_string())
+            .or_default()
+            .push_back(None);
+    }
+
+    /// 上报一个事件，缺省的 `time`、`self_id` 会被补全，消息事件缺省时分配新的 `message_id`
+    pub fn push(&self, mut event: Value) {
+        if let Some(obj) = event.as_object_mut() {
+            obj.entry("time").or_insert(json!(0));
+            obj.entry("self_id").or_insert(json!(self.self_id));
+            if obj.get("post_type") == Some(&json!("message")) {
+                obj.entry("message_id")
+                    .or_insert_with(|| json!(MESSAGE_ID.fetch_add(1, Ordering::Relaxed)));
+            }
+        }
+        self.events.send(event.to_string()).unwrap();
+    }
+
+    /// 按行上报录制的事件，忽略空行
+    pub fn replay(&self, jsonl: &str) {
+        for line in jsonl.lines().filter(|l| !l.trim().is_empty()) {
+            self.push(serde_json::from_str(line).expect("invalid event json"));
+        }
+    }
+
+    /// 目前为止收到的所有动作
+    pub fn actions(&self) -> Vec<Action> {
+        self.recorder.actions.lock().unwrap().clone()
+    }
+
+    /// 等待并返回第一个名为 `action` 的动作，超时则测试失败
+    pub async fn wait_action(&self, action: &str) -> Action {
+        self.wait_actions(action, 1).await.remove(0)
+    }
+
+    /// 等待至少 `count` 个名为 `action` 的动作，按收到的顺序返回
+    pub async fn wait_actions(&self, action: &str, count: usize) -> Vec<Action> {
+        let found = tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let notified = self.recorder.notify.notified();
+                let actions: Vec<Action> = self
+                    .actions()
+                    .into_iter()
+                    .filter(|a| a.action == action)
+                    .collect();
+                if actions.len() >= count {
+                    return actions;
+                }
+                notified.await;
+            }
+        })
+        .await;
+        found.unwrap_or_else(|_| panic!("{} x{} not received", action, count))
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        // 任务被中止，`event_loop` 来不及注销，这里代为注销，以免之后的测试取到失效的账号
+        protocol::remove(self.self_id, &self.sender);
+    }
+}
+
+async fn serve(
+    listener: TcpListener,
+    self_id: i64,
+    mut events: mpsc::UnboundedReceiver<String>,
+    responses: Arc<Responses>,
+    recorder: Arc<Recorder>,
+) {
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut ws = accept_async(stream).await.unwrap();
+    let lifecycle = json!({
+        "time": 0,
+        "self_id": self_id,
+        "post_type": "meta_event",
+        "meta_event_type": "lifecycle",
+        "sub_type": "connect",
+    });
+    ws.send(Message::from(lifecycle.to_string())).await.unwrap();
+
+    loop {
+        tokio::select! {
+            frame = ws.next() => {
+                let Some(Ok(Message::Text(text))) = frame else {
+                    return;
+                };
+                let req: Value = serde_json::from_str(&text).unwrap();
+                let action = Action {
+                    action: req["action"].as_str().unwrap_or_default().to_string(),
+                    params: req["params"].clone(),
+                };
+                let response = responses.next(&action.action);
+                recorder.actions.lock().unwrap().push(action);
+                recorder.notify.notify_waiters();
+                let Some(mut res) = response else {
+                    continue;
+                };
+                res["echo"] = req["echo"].clone();
+                if ws.send(Message::from(res.to_string())).await.is_err() {
+                    return;
+                }
+            }
+            Some(event) = events.recv() => {
+                if ws.send(Message::from(event)).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// 群消息事件
+pub fn group_message(group_id: i64, user_id: i64, text: &str) -> Value {
+    json!({
+        "post_type": "message",
+        "message_type": "group",
+        "sub_type": "normal",
+        "group_id": group_id,
+        "user_id": user_id,
+        "message": [{ "type": "text", "data": { "text": text } }],
+        "raw_message": text,
+        "font": 0,
+        "sender": { "user_id": user_id, "nickname": "tester" },
+    })
+}
+
+/// 私聊消息事件
+pub fn private_message(user_id: i64, text: &str) -> Value {
+    json!({
+        "post_type": "message",
+        "message_type": "private",
+        "sub_type": "friend",
+        "user_id": user_id,
+        "message": [{ "type": "text", "data": { "text": text } }],
+        "raw_message": text,
+        "font": 0,
+        "sender": { "user_id": user_id, "nickname": "tester" },
+    })
+}
+
+/// 在本地端口返回固定 JSON 的 HTTP 服务，用于替代应用访问的外部 API
+pub async fn http_fixture(body: Value) -> String {
+    http_fixture_with("200 OK", body.to_string()).await
+}
+
+/// 以指定状态行与原始正文应答的 HTTP 服务，用于模拟失败的接口
+pub async fn http_fixture_with(status: &'static str, body: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 4096];
+            _ = stream.read(&mut buf).await;
+            let res = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            _ = stream.write_all(res.as_bytes()).await;
+        }
+    });
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockServer, failed, ok};
+    use crate::protocol::{bots, error::ActionError, get_bot_by_id};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn drop_unregisters_bot() {
+        let server = MockServer::start(10010, "token = \"\"\nowner = 1\n").await;
+        assert!(get_bot_by_id(10010).sender.is_some());
+        drop(server);
+        assert!(!bots().contains(&10010));
+        assert!(get_bot_by_id(10010).sender.is_none());
+    }
+
+    #[tokio::test]
+    async fn scripted_responses() {
+        let server = MockServer::start(10017, "token = \"\"\nowner = 1\n").await;
+        let bot = get_bot_by_id(10017);
+        let first = bot.send_private_message(2, "a").await.unwrap().unwrap();
+        let second = bot.send_private_message(2, "b").await.unwrap().unwrap();
+        assert_ne!(first.message_id, second.message_id);
+
+        server.respond_once("send_private_msg", failed(1200, "blocked"));
+        let err = bot.send_private_message(2, "c").await.unwrap_err();
+        assert!(matches!(err, ActionError::Failed { retcode: 1200, .. }));
+        assert!(bot.send_private_message(2, "d").await.is_ok());
+
+        server.respond(
+            "get_login_info",
+            ok(json!({ "user_id": 10017, "nickname": "k" })),
+        );
+        assert_eq!(bot.get_login_info().await.unwrap().user_id, 10017);
+
+        server.ignore_once("get_login_info");
+        let pending =
+            tokio::time::timeout(std::time::Duration::from_millis(200), bot.get_login_info()).await;
+        assert!(pending.is_err());
+    }
+}
@@ -1,16 +1,30 @@
-use crate::protocol::adapter::{Request, Response};
-use anyhow::{Result, anyhow};
+use crate::protocol::{
+    adapter::{Request, Response},
+    error::{ActionError, Result},
+};
+use dashmap::DashMap;
 use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use tokio::sync::Mutex;
+use std::sync::Mutex;
 use tokio::sync::{mpsc::Sender, oneshot};
 use uuid::Uuid;
 
+pub mod error;
 pub mod event;
 pub mod message;
+pub mod response;
 
 pub mod adapter;
 mod extension;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(test)]
+pub mod mock;
+mod record;
+mod replay;
+mod tagged;
+pub mod transport;
 
 type RequestSender = Sender<Request>;
 
@@ -32,23 +46,92 @@ impl Protocol {
 
         self.sender
             .clone()
-            .ok_or(anyhow!("sender not found"))?
+            .ok_or(ActionError::NotConnected)?
             .send(request)
-            .await?;
-        Ok(rx.await?)
+            .await
+            .map_err(|_| ActionError::ChannelClosed)?;
+        rx.await.map_err(|_| ActionError::ChannelClosed)?
+    }
+
+    /// 发送请求并将响应数据反序列化为 `T`，实现以 `async` 受理时返回 `None`
+    async fn call_optional<T: DeserializeOwned>(
+        &self,
+        func: &str,
+        data: Value,
+    ) -> Result<Option<T>> {
+        self.send_request(func, data).await?.into_data()
+    }
+
+    /// 发送请求并将响应数据反序列化为 `T`，用于总是返回数据的查询
+    async fn call<T: DeserializeOwned>(&self, func: &str, data: Value) -> Result<T> {
+        self.call_optional(func, data)
+            .await?
+            .ok_or(ActionError::NoData)
+    }
+
+    /// 发送无响应数据的请求，仅检查执行状态
+    async fn call_unit(&self, func: &str, data: Value) -> Result<()> {
+        self.send_request(func, data).await?.check()?;
+        Ok(())
     }
 }
 
 lazy_static! {
-    pub static ref BOT: Mutex<Protocol> = Mutex::new(Protocol { sender: None });
+    /// 已连接的账号，以 `self_id` 为键
+    static ref BOTS: DashMap<i64, Protocol> = DashMap::new();
+    /// 默认账号，即最先连接且仍在线的账号
+    static ref MAIN: Mutex<Option<i64>> = Mutex::new(None);
 }
 
+/// 获取默认账号，不关心由哪个账号发送时使用
 pub async fn get_bot() -> Protocol {
-    BOT.lock().await.clone()
+    match *MAIN.lock().unwrap() {
+        Some(self_id) => get_bot_by_id(self_id),
+        None => Protocol { sender: None },
+    }
+}
+
+/// 获取指定账号，未连接时调用动作会返回 `ActionError::NotConnected`
+pub fn get_bot_by_id(self_id: i64) -> Protocol {
+    BOTS.get(&self_id)
+        .map(|bot| bot.clone())
+        .unwrap_or(Protocol { sender: None })
+}
+
+/// 所有在线账号的 `self_id`
+#[allow(unused)]
+pub fn bots() -> Vec<i64> {
+    BOTS.iter().map(|bot| *bot.key()).collect()
+}
+
+pub async fn update(self_id: i64, sender: RequestSender) {
+    BOTS.insert(
+        self_id,
+        Protocol {
+            sender: Some(sender),
+        },
+    );
+    let mut main = MAIN.lock().unwrap();
+    if main.is_none() {
+        *main = Some(self_id);
+    }
+    log::info!("Bot {} registered", self_id);
 }
 
-pub async fn update(sender: RequestSender) {
-    *BOT.lock().await = Protocol {
-        sender: Some(sender),
-    };
+/// 连接断开时注销账号，若该账号已被新连接替换则忽略，返回是否注销
+pub fn remove(self_id: i64, sender: &RequestSender) -> bool {
+    let removed = BOTS
+        .remove_if(&self_id, |_, bot| {
+            bot.sender.as_ref().is_some_and(|s| s.same_channel(sender))
+        })
+        .is_some();
+    if !removed {
+        return false;
+    }
+    let mut main = MAIN.lock().unwrap();
+    if *main == Some(self_id) {
+        *main = BOTS.iter().next().map(|bot| *bot.key());
+    }
+    log::info!("Bot {} unregistered", self_id);
+    true
 }
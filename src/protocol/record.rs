@@ -0,0 +1,98 @@
+// 原始帧录制，配置 `record` 后将收发的每一帧追加写入 JSONL 文件
+//
+// 每行形如 `{"time":"...","dir":"in","frame":{...}}`，可通过 `mode = "replay"` 回放
+use crate::config::{self, Mode};
+use chrono::SecondsFormat;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::mpsc,
+};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// OneBot 实现上报的事件与动作响应
+    In,
+    /// bot 发出的动作请求
+    Out,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub time: String,
+    pub dir: Direction,
+    /// 能解析为 JSON 时按 JSON 保存，否则保存原始文本
+    pub frame: Value,
+}
+
+impl Entry {
+    /// 还原为收发时的文本帧
+    pub fn text(&self) -> String {
+        match &self.frame {
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        }
+    }
+}
+
+lazy_static! {
+    /// 写入线程的发送端，打开与写入文件都在该线程中进行，不阻塞收发帧的任务
+    ///
+    /// 使用独立线程而非 tokio 任务，不依赖首次录制时所在的运行时
+    static ref WRITER: mpsc::Sender<(String, String)> = spawn_writer();
+}
+
+pub fn inbound(frame: &str) {
+    write(Direction::In, frame);
+}
+
+pub fn outbound(frame: &str) {
+    write(Direction::Out, frame);
+}
+
+fn write(dir: Direction, frame: &str) {
+    let config = config::get();
+    if config.record.is_empty() || config.mode == Mode::Replay {
+        return;
+    }
+    let entry = Entry {
+        time: chrono::Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+        dir,
+        frame: serde_json::from_str(frame).unwrap_or_else(|_| Value::String(frame.to_string())),
+    };
+    let Ok(mut line) = serde_json::to_string(&entry) else {
+        return;
+    };
+    line.push('\n');
+    _ = WRITER.send((config.record.clone(), line));
+}
+
+/// 依次写入收到的行，路径变化时重新打开文件
+fn spawn_writer() -> mpsc::Sender<(String, String)> {
+    let (tx, rx) = mpsc::channel::<(String, String)>();
+    std::thread::spawn(move || {
+        let mut file: Option<(String, File)> = None;
+        for (path, line) in rx {
+            if file.as_ref().is_none_or(|(opened, _)| *opened != path) {
+                match OpenOptions::new().create(true).append(true).open(&path) {
+                    Ok(f) => file = Some((path, f)),
+                    Err(e) => {
+                        log::warn!("failed to open {}: {}", path, e);
+                        file = None;
+                        continue;
+                    }
+                }
+            }
+            if let Some((path, f)) = file.as_mut()
+                && let Err(e) = f.write_all(line.as_bytes())
+            {
+                log::warn!("failed to write {}: {}", path, e);
+            }
+        }
+    });
+    tx
+}
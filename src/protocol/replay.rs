@@ -0,0 +1,228 @@
+// 回放 `record` 录制的 JSONL 文件
+//
+// 录制的事件依次经内存传输送入事件循环，bot 发出的动作由录制时的响应按动作名依次应答，
+// 没有录制响应的动作返回空数据。bot 发出的动作数达到录制时的数量后空闲一段时间即结束
+use crate::protocol::{
+    adapter, bots,
+    record::{Direction, Entry},
+    transport::{MemoryTransport, Transport},
+};
+use anyhow::{Result, anyhow};
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// 动作数达到录制时的数量后，超过此时间没有新的动作请求即认为回放结束
+const IDLE: Duration = Duration::from_secs(1);
+/// 动作数一直少于录制时的数量时，最多等待 `IDLE` 的倍数
+const MAX_IDLE_ROUNDS: u32 = 10;
+
+pub async fn run(path: &str) -> Result<()> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| anyhow!("failed to read {}: {}", path, e))?;
+    let entries = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<Entry>, _>>()?;
+    log::info!("replaying {} frames from {}", entries.len(), path);
+    let requests = play(&entries, IDLE).await?;
+    log::info!("replay finished, {} requests sent", requests.len());
+    Ok(())
+}
+
+/// 回放录制的帧，返回 bot 发出的动作请求
+pub(crate) async fn play(entries: &[Entry], idle: Duration) -> Result<Vec<Value>> {
+    let expected = entries.iter().filter(|e| e.dir == Direction::Out).count();
+    let responses = Arc::new(Mutex::new(responses(entries)));
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let self_id = entries
+        .iter()
+        .filter(|e| e.dir == Direction::In)
+        .find_map(|e| e.frame.get("self_id").and_then(|v| v.as_i64()));
+
+    let (bot, backend) = MemoryTransport::pair();
+    let backend = Arc::new(backend);
+    let event_loop = tokio::spawn(adapter::event_loop(bot, self_id));
+
+    let last_request = Arc::new(Mutex::new(Instant::now()));
+    let responder = {
+        let backend = backend.clone();
+        let last_request = last_request.clone();
+        let requests = requests.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(frame)) = backend.recv().await {
+                *last_request.lock().unwrap() = Instant::now();
+                let Ok(req) = serde_json::from_str::<Value>(&frame) else {
+                    continue;
+                };
+                requests.lock().unwrap().push(req.clone());
+                let action = req["action"].as_str().unwrap_or_default();
+                let mut res = responses
+                    .lock()
+                    .unwrap()
+                    .get_mut(action)
+                    .and_then(|q| q.pop_front())
+                    .unwrap_or_else(|| json!({ "status": "ok", "retcode": 0, "data": null }));
+                res["echo"] = req["echo"].clone();
+                _ = backend.send(res.to_string()).await;
+            }
+        })
+    };
+
+    // 等待应用加载完成、账号注册后再上报事件
+    let registered = || match self_id {
+        Some(self_id) => bots().contains(&self_id),
+        None => !bots().is_empty(),
+    };
+    while !registered() {
+        if event_loop.is_finished() {
+            responder.abort();
+            event_loop.await??;
+            return Ok(Vec::new());
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    for entry in entries.iter().filter(|e| e.dir == Direction::In) {
+        if entry.frame.get("echo").is_none() {
+            backend.send(entry.text()).await?;
+        }
+    }
+
+    let started = Instant::now();
+    *last_request.lock().unwrap() = started;
+    loop {
+        tokio::time::sleep(idle / 4).await;
+        let sent = requests.lock().unwrap().len();
+        if sent >= expected && last_request.lock().unwrap().elapsed() >= idle {
+            break;
+        }
+        if started.elapsed() >= idle * MAX_IDLE_ROUNDS {
+            log::warn!("replay expected {} requests, got {}", expected, sent);
+            break;
+        }
+    }
+
+    backend.close().await?;
+    responder.abort();
+    event_loop.await??;
+    let requests = requests.lock().unwrap().clone();
+    Ok(requests)
+}
+
+/// 按 `echo` 将录制的响应与请求配对，以动作名分组
+fn responses(entries: &[Entry]) -> HashMap<String, VecDeque<Value>> {
+    let actions: HashMap<&str, &str> = entries
+        .iter()
+        .filter(|e| e.dir == Direction::Out)
+        .filter_map(|e| Some((e.frame["echo"].as_str()?, e.frame["action"].as_str()?)))
+        .collect();
+
+    let mut responses: HashMap<String, VecDeque<Value>> = HashMap::new();
+    for entry in entries.iter().filter(|e| e.dir == Direction::In) {
+        if let Some(action) = entry.frame["echo"].as_str().and_then(|e| actions.get(e)) {
+            responses
+                .entry(action.to_string())
+                .or_default()
+                .push_back(entry.frame.clone());
+        }
+    }
+    responses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::play;
+    use crate::protocol::{
+        mock::{MockServer, private_message},
+        record::Entry,
+    };
+    use serde_json::json;
+    use std::time::{Duration, Instant};
+
+    const IDLE: Duration = Duration::from_millis(100);
+
+    fn read(path: &std::path::Path) -> Vec<Entry> {
+        std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn recorded_session_replays_through_dispatch() {
+        let file = std::env::temp_dir().join("kanami-test-record.jsonl");
+        _ = std::fs::remove_file(&file);
+        let config = format!(
+            "token = \"\"\nowner = 1\nrecord = {:?}\n",
+            file.display().to_string()
+        );
+        let server = MockServer::start(10014, &config).await;
+        server.push(private_message(2, "!help"));
+        server.wait_action("send_private_msg").await;
+        // 写入在独立线程中进行，等待动作的响应也落盘
+        let entries = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let entries = read(&file);
+                if entries
+                    .iter()
+                    .any(|e| e.frame.get("echo").is_some() && e.frame.get("status").is_some())
+                {
+                    return entries;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("response not recorded");
+        drop(server);
+
+        // 另起一个模拟服务器持有全局锁，回放模式下不再录制
+        let _server = MockServer::start(10015, &format!("{}mode = \"replay\"\n", config)).await;
+        // 换一个账号回放，以免录制时的消息被去重
+        let mut entries = entries;
+        for entry in &mut entries {
+            if entry.frame.get("self_id").is_some() {
+                entry.frame["self_id"] = 20014.into();
+            }
+        }
+        let requests = play(&entries, IDLE).await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["action"], "send_private_msg");
+        assert_eq!(requests[0]["params"]["user_id"], 2);
+        assert_eq!(read(&file).len(), entries.len());
+        _ = std::fs::remove_file(&file);
+    }
+
+    #[tokio::test]
+    async fn replay_gives_up_on_missing_requests() {
+        let _server = MockServer::start(10016, "token = \"\"\nowner = 1\n").await;
+        let entries: Vec<Entry> = [
+            json!({ "time": "", "dir": "in", "frame": {
+                "time": 0,
+                "self_id": 20016,
+                "post_type": "meta_event",
+                "meta_event_type": "lifecycle",
+                "sub_type": "connect",
+            } }),
+            json!({ "time": "", "dir": "out", "frame": {
+                "action": "send_private_msg",
+                "params": {},
+                "echo": "1",
+            } }),
+        ]
+        .into_iter()
+        .map(|v| serde_json::from_value(v).unwrap())
+        .collect();
+
+        let started = Instant::now();
+        let requests = play(&entries, IDLE).await.unwrap();
+        assert!(requests.is_empty());
+        assert!(started.elapsed() >= IDLE * super::MAX_IDLE_ROUNDS);
+    }
+}
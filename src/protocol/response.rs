@@ -0,0 +1,192 @@
+// OneBot 动作的响应数据
+// https://github.com/botuniverse/onebot-11/blob/master/api/public.md
+
+use crate::protocol::{event::GroupRole, message::Message};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// 发送消息的响应
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct SentMessage {
+    pub message_id: i32,
+}
+
+/// 发送合并转发的响应
+#[cfg(feature = "napcat")]
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct SentForward {
+    pub message_id: i32,
+    pub res_id: Option<String>,
+}
+
+/// 消息发送者，私聊时群相关字段为空
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct MessageSender {
+    pub user_id: i64,
+    pub nickname: String,
+    pub card: Option<String>,
+    pub role: Option<GroupRole>,
+}
+
+/// `get_msg` 的响应
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct MessageDetail {
+    pub time: i64,
+    pub message_type: String,
+    pub message_id: i32,
+    pub real_id: Option<i32>,
+    pub sender: MessageSender,
+    pub message: Message,
+}
+
+/// `get_forward_msg` 的响应
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct ForwardMessage {
+    /// NapCat 返回 `messages`
+    #[serde(alias = "messages")]
+    pub message: Message,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct LoginInfo {
+    pub user_id: i64,
+    pub nickname: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct StrangerInfo {
+    pub user_id: i64,
+    pub nickname: String,
+    pub sex: Option<String>,
+    pub age: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct FriendInfo {
+    pub user_id: i64,
+    pub nickname: String,
+    pub remark: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct GroupInfo {
+    pub group_id: i64,
+    pub group_name: String,
+    pub member_count: i32,
+    pub max_member_count: i32,
+}
+
+/// 群成员信息，获取列表时 `area`、`title` 等字段可能为空
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct GroupMember {
+    pub group_id: i64,
+    pub user_id: i64,
+    pub nickname: String,
+    pub card: Option<String>,
+    pub sex: Option<String>,
+    pub age: Option<i32>,
+    pub area: Option<String>,
+    pub join_time: i64,
+    pub last_sent_time: i64,
+    pub level: Option<String>,
+    pub role: GroupRole,
+    pub unfriendly: Option<bool>,
+    pub title: Option<String>,
+    pub title_expire_time: Option<i64>,
+    pub card_changeable: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct CurrentTalkative {
+    pub user_id: i64,
+    pub nickname: String,
+    pub avatar: String,
+    pub day_count: i32,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct HonorMember {
+    pub user_id: i64,
+    pub nickname: String,
+    pub avatar: String,
+    pub description: String,
+}
+
+/// 群荣誉信息，只有请求的类型有数据
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct GroupHonorInfo {
+    pub group_id: i64,
+    pub current_talkative: Option<CurrentTalkative>,
+    pub talkative_list: Option<Vec<HonorMember>>,
+    pub performer_list: Option<Vec<HonorMember>>,
+    pub legend_list: Option<Vec<HonorMember>>,
+    pub strong_newbie_list: Option<Vec<HonorMember>>,
+    pub emotion_list: Option<Vec<HonorMember>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct Cookies {
+    pub cookies: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct CsrfToken {
+    pub token: i32,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct Credentials {
+    pub cookies: String,
+    pub csrf_token: i32,
+}
+
+/// `get_record`、`get_image` 的响应
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct FileInfo {
+    pub file: String,
+}
+
+/// `can_send_image`、`can_send_record` 的响应
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct CanSend {
+    pub yes: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct Status {
+    pub online: Option<bool>,
+    pub good: bool,
+    /// OneBot 实现自行添加的其它内容
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct VersionInfo {
+    pub app_name: String,
+    pub app_version: String,
+    pub protocol_version: String,
+    /// OneBot 实现自行添加的其它内容
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
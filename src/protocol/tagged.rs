@@ -0,0 +1,50 @@
+// 带标签的枚举中未识别类型的兜底
+//
+// `#[serde(untagged)]` 兜底变体会吞掉已知类型的解析错误，因此先读出标签：已知类型交给派生的实现，
+// 解析失败时原样返回错误，只有未定义的标签才落到兜底变体
+use serde::{
+    Deserialize, Deserializer,
+    de::{Error, Unexpected},
+};
+use serde_json::Value;
+
+/// 按 `tag` 字段解析，`known` 为枚举已定义的标签（含别名）
+pub(crate) fn deserialize<'de, D, T>(
+    deserializer: D,
+    tag: &str,
+    known: &[&str],
+    parse: impl FnOnce(Value) -> serde_json::Result<T>,
+    unknown: impl FnOnce(String, Value) -> serde_json::Result<T>,
+) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut value = Value::deserialize(deserializer)?;
+    let kind = match value.get(tag) {
+        Some(Value::String(kind)) => kind.clone(),
+        Some(other) => {
+            return Err(D::Error::invalid_type(unexpected(other), &"a string tag"));
+        }
+        None => return Err(D::Error::custom(format!("missing field `{}`", tag))),
+    };
+    let result = if known.contains(&kind.as_str()) {
+        parse(value)
+    } else {
+        if let Some(map) = value.as_object_mut() {
+            map.remove(tag);
+        }
+        unknown(kind, value)
+    };
+    result.map_err(D::Error::custom)
+}
+
+fn unexpected(value: &Value) -> Unexpected<'_> {
+    match value {
+        Value::Null => Unexpected::Unit,
+        Value::Bool(b) => Unexpected::Bool(*b),
+        Value::Number(_) => Unexpected::Other("number"),
+        Value::String(s) => Unexpected::Str(s),
+        Value::Array(_) => Unexpected::Seq,
+        Value::Object(_) => Unexpected::Map,
+    }
+}
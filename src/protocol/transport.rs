@@ -0,0 +1,165 @@
+// 与 OneBot 实现之间收发文本帧的传输层，事件循环只依赖此处的 `Transport`
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use futures_util::{
+    SinkExt, StreamExt,
+    stream::{SplitSink, SplitStream},
+};
+use tokio::sync::{Mutex, mpsc};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, tungstenite::Message};
+
+pub type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// 双向文本帧传输，收发可在不同任务中同时进行
+#[async_trait]
+pub trait Transport: Send + Sync + 'static {
+    /// 发送一帧
+    async fn send(&self, frame: String) -> Result<()>;
+    /// 接收一帧，连接关闭时返回 `None`
+    async fn recv(&self) -> Option<Result<String>>;
+    /// 关闭连接，之后对端的 `recv` 返回 `None`
+    async fn close(&self) -> Result<()>;
+}
+
+#[async_trait]
+impl<T: Transport> Transport for std::sync::Arc<T> {
+    async fn send(&self, frame: String) -> Result<()> {
+        T::send(self, frame).await
+    }
+
+    async fn recv(&self) -> Option<Result<String>> {
+        T::recv(self).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        T::close(self).await
+    }
+}
+
+/// WebSocket 传输，忽略非文本帧
+pub struct WsTransport {
+    sink: Mutex<SplitSink<WsStream, Message>>,
+    stream: Mutex<SplitStream<WsStream>>,
+}
+
+impl WsTransport {
+    pub fn new(ws: WsStream) -> Self {
+        let (sink, stream) = ws.split();
+        Self {
+            sink: Mutex::new(sink),
+            stream: Mutex::new(stream),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for WsTransport {
+    async fn send(&self, frame: String) -> Result<()> {
+        Ok(self.sink.lock().await.send(Message::from(frame)).await?)
+    }
+
+    async fn recv(&self) -> Option<Result<String>> {
+        let mut stream = self.stream.lock().await;
+        loop {
+            match stream.next().await? {
+                Ok(Message::Text(text)) => return Some(Ok(text.to_string())),
+                Ok(Message::Close(_)) => return None,
+                Ok(Message::Ping(_) | Message::Pong(_)) => continue,
+                Ok(msg) => log::warn!("unknown msg type: {:?}", msg),
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<()> {
+        Ok(self.sink.lock().await.close().await?)
+    }
+}
+
+/// 内存传输，由 `MemoryTransport::pair` 创建一对互相连接的端点
+pub struct MemoryTransport {
+    tx: Mutex<Option<mpsc::UnboundedSender<String>>>,
+    rx: Mutex<mpsc::UnboundedReceiver<String>>,
+}
+
+impl MemoryTransport {
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, a_rx) = mpsc::unbounded_channel();
+        let (b_tx, b_rx) = mpsc::unbounded_channel();
+        let a = Self {
+            tx: Mutex::new(Some(a_tx)),
+            rx: Mutex::new(b_rx),
+        };
+        let b = Self {
+            tx: Mutex::new(Some(b_tx)),
+            rx: Mutex::new(a_rx),
+        };
+        (a, b)
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn send(&self, frame: String) -> Result<()> {
+        self.tx
+            .lock()
+            .await
+            .as_ref()
+            .ok_or(anyhow!("transport closed"))?
+            .send(frame)
+            .map_err(|_| anyhow!("transport closed"))
+    }
+
+    async fn recv(&self) -> Option<Result<String>> {
+        self.rx.lock().await.recv().await.map(Ok)
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.tx.lock().await.take();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryTransport, Transport};
+    use crate::protocol::{
+        adapter, bots,
+        mock::{MockServer, private_message},
+    };
+    use serde_json::{Value, json};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn event_loop_runs_over_memory() {
+        // 借用模拟服务器的配置与全局锁，bot 本身只经过内存传输
+        let _server = MockServer::start(10013, "token = \"\"\nowner = 1\n").await;
+        let (bot, backend) = MemoryTransport::pair();
+        let event_loop = tokio::spawn(adapter::event_loop(bot, Some(20013)));
+        while !bots().contains(&20013) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let mut event = private_message(2, "!help");
+        event["time"] = 0.into();
+        event["self_id"] = 20013.into();
+        // 与模拟服务器分配的递增 id 错开，以免被去重
+        event["message_id"] = (-1).into();
+        backend.send(event.to_string()).await.unwrap();
+
+        let frame = tokio::time::timeout(Duration::from_secs(5), backend.recv())
+            .await
+            .expect("no request received")
+            .unwrap()
+            .unwrap();
+        let req: Value = serde_json::from_str(&frame).unwrap();
+        assert_eq!(req["action"], "send_private_msg");
+        assert_eq!(req["params"]["user_id"], 2);
+        let res = json!({ "status": "ok", "retcode": 0, "data": null, "echo": req["echo"] });
+        backend.send(res.to_string()).await.unwrap();
+
+        backend.close().await.unwrap();
+        event_loop.await.unwrap().unwrap();
+        assert!(!bots().contains(&20013));
+    }
+}